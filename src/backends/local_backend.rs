@@ -0,0 +1,253 @@
+use std::{fs, path::{Path, PathBuf}, time::Instant};
+
+use crate::models::{command::Command, scheduler::Cloud, transfer_outcome::TransferOutcome};
+
+use super::transfer_backend::TransferBackend;
+
+/// Copies files into a local or mounted directory (NAS share, USB drive)
+/// without going through rclone.
+pub struct LocalBackend {
+    pub cloud: Cloud,
+    pub target: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(cloud: Cloud, target: PathBuf) -> Self {
+        Self { cloud, target }
+    }
+
+    pub fn destination(&self, command: &Command, root: &str) -> PathBuf {
+        let mut destination = self.resolve(root);
+
+        for segment in command.remote_segments() {
+            destination.push(segment);
+        }
+
+        destination
+    }
+
+    /// Maps a `/`-separated remote path onto the target directory.
+    fn resolve(&self, remote: &str) -> PathBuf {
+        let mut path = self.target.clone();
+
+        for segment in remote.split(['/', '\\']) {
+            if !segment.is_empty() {
+                path.push(segment);
+            }
+        }
+
+        path
+    }
+
+    fn copy(&self, command: &Command, root: &str) -> std::io::Result<u64> {
+        let source = Path::new(&command.local_path);
+        let destination = self.destination(command, root);
+
+        fs::create_dir_all(&destination)?;
+
+        let file_name = source.file_name().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} is not a file", command.local_path))
+        })?;
+
+        if command.link {
+            return copy_link(source, &destination.join(file_name));
+        }
+
+        fs::copy(source, destination.join(file_name))
+    }
+}
+
+impl TransferBackend for LocalBackend {
+    fn transfer(&self, command: &Command, root: &str) -> TransferOutcome {
+        let started = Instant::now();
+        let result = self.copy(command, root);
+        let duration = started.elapsed();
+
+        let (exit_code, bytes, stderr) = match result {
+            Ok(bytes) => (Some(0), bytes, String::new()),
+            Err(error) => (None, 0, error.to_string()),
+        };
+
+        TransferOutcome {
+            cloud: self.cloud,
+            local_path: command.local_path.clone(),
+            target: self.target(command, root),
+            exit_code,
+            duration,
+            bytes,
+            stderr,
+            attempts: 1,
+        }
+    }
+
+    fn target(&self, command: &Command, root: &str) -> String {
+        let destination = self.destination(command, root);
+
+        match Path::new(&command.local_path).file_name() {
+            Some(file_name) => destination.join(file_name).to_string_lossy().to_string(),
+            None => destination.to_string_lossy().to_string(),
+        }
+    }
+
+    fn list_snapshots(&self, parent: &str) -> std::io::Result<Vec<String>> {
+        let parent = self.resolve(parent);
+
+        if !parent.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut names = vec![];
+
+        for entry in fs::read_dir(parent)?.flatten() {
+            if entry.path().is_dir() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        Ok(names)
+    }
+
+    fn purge(&self, path: &str) -> std::io::Result<()> {
+        fs::remove_dir_all(self.resolve(path))
+    }
+
+    fn list_files(&self, root: &str) -> std::io::Result<Vec<String>> {
+        let root = self.resolve(root);
+        let mut files = vec![];
+        let mut dirs = vec![root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)?.flatten() {
+                let path = entry.path();
+
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+
+                if let Ok(relative) = path.strip_prefix(&root) {
+                    let segments = relative
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy().to_string())
+                        .collect::<Vec<String>>();
+
+                    files.push(segments.join("/"));
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn download(&self, root: &str, remote_file: &str, destination: &Path) -> std::io::Result<()> {
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::copy(self.resolve(&format!("{}/{}", root, remote_file)), destination).map(|_| ())
+    }
+}
+
+/// Recreates the symlink `source` at `destination`, pointing at the same
+/// target. Counts as zero bytes.
+fn copy_link(source: &Path, destination: &Path) -> std::io::Result<u64> {
+    let target = fs::read_link(source)?;
+
+    if fs::symlink_metadata(destination).is_ok() {
+        fs::remove_file(destination)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, destination)?;
+
+    #[cfg(windows)]
+    match source.is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, destination)?,
+        false => std::os::windows::fs::symlink_file(target, destination)?,
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::backends::local_backend::LocalBackend;
+    use crate::filesystems::local_file_system::LocalFileSystem;
+    use crate::mappers::{dir_entry_to_commands, template_to_dir_entry};
+    use crate::models::content_policy::ContentPolicy;
+    use crate::models::link_policy::LinkPolicy;
+    use crate::models::retry_policy::RetryPolicy;
+    use crate::models::scheduler::Cloud;
+    use crate::services::transfer_service;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watcher_backup_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn backs_up_the_planned_files_into_the_target() {
+        let dir = temp_dir("local_backend_pipeline");
+        let source = dir.join("source");
+        let target = dir.join("target");
+
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        fs::write(source.join("b.log"), "b").unwrap();
+        fs::write(source.join("sub/c.txt"), "c").unwrap();
+
+        let template = format!("{}>2[{{\\.txt$,,1}}]", source.display());
+        let entries = template_to_dir_entry::map(template, None).unwrap();
+        let commands = dir_entry_to_commands::map(entries, &LocalFileSystem, &ContentPolicy::default(), LinkPolicy::Follow, 2, &|_, _| {}).unwrap();
+
+        let backend = LocalBackend::new(Cloud::Local, target.clone());
+        let report = transfer_service::run(&backend, &commands, "host/daily/snapshot", &RetryPolicy::default(), 2, &|_| {});
+
+        assert_eq!(report.succeeded.len(), 2);
+        assert!(report.gave_up.is_empty());
+
+        let copy = target.join("host/daily/snapshot").join(source.strip_prefix("/").unwrap_or(&source));
+
+        assert_eq!(fs::read_to_string(copy.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(copy.join("sub/c.txt")).unwrap(), "c");
+        assert!(!copy.join("b.log").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+    #[cfg(unix)]
+    #[test]
+    fn records_walked_links_but_copies_what_template_links_point_to() {
+        let dir = temp_dir("local_backend_links");
+        let source = dir.join("source");
+        let target = dir.join("target");
+
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(source.join("a.txt"), source.join("sub/walked.txt")).unwrap();
+        std::os::unix::fs::symlink(source.join("a.txt"), source.join("root.txt")).unwrap();
+
+        let template = format!("{}>s\n{}>s", source.join("sub").display(), source.join("root.txt").display());
+        let entries = template_to_dir_entry::map(template, None).unwrap();
+        let commands = dir_entry_to_commands::map(entries, &LocalFileSystem, &ContentPolicy::default(), LinkPolicy::Record, 1, &|_, _| {}).unwrap();
+
+        let backend = LocalBackend::new(Cloud::Local, target.clone());
+        let report = transfer_service::run(&backend, &commands, "snapshot", &RetryPolicy::default(), 1, &|_| {});
+
+        assert_eq!(report.succeeded.len(), 2);
+
+        let copy = target.join("snapshot").join(source.strip_prefix("/").unwrap_or(&source));
+
+        assert_eq!(fs::read_link(copy.join("sub/walked.txt")).unwrap(), source.join("a.txt"));
+        assert!(!fs::symlink_metadata(copy.join("root.txt")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(copy.join("root.txt")).unwrap(), "a");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
\ No newline at end of file
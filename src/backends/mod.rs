@@ -0,0 +1,3 @@
+pub mod transfer_backend;
+pub mod rclone_backend;
+pub mod local_backend;
@@ -0,0 +1,133 @@
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+use std::{fs, path::Path, time::Instant};
+
+use crate::models::{command::Command, scheduler::Cloud, transfer_outcome::TransferOutcome};
+
+use super::transfer_backend::TransferBackend;
+
+/// Uploads through `rclone copy` to a configured rclone remote.
+pub struct RcloneBackend {
+    pub cloud: Cloud,
+    pub remote: String,
+    pub speed: f32,
+}
+
+impl RcloneBackend {
+    pub fn new(cloud: Cloud, remote: String, speed: f32) -> Self {
+        Self { cloud, remote, speed }
+    }
+
+    fn rclone(&self) -> std::process::Command {
+        #[allow(unused_mut)]
+        let mut rclone = std::process::Command::new("rclone");
+
+        #[cfg(target_os = "windows")]
+        {
+            rclone.creation_flags(0x08000000);
+        }
+
+        rclone
+    }
+
+    fn run(&self, rclone: &mut std::process::Command) -> std::io::Result<String> {
+        let output = rclone.output()?;
+
+        match output.status.success() {
+            true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+            false => Err(std::io::Error::other(TransferOutcome::trim_stderr(&String::from_utf8_lossy(&output.stderr)))),
+        }
+    }
+}
+
+impl TransferBackend for RcloneBackend {
+    fn transfer(&self, command: &Command, root: &str) -> TransferOutcome {
+        let target = self.target(command, root);
+        let mut rclone = self.rclone();
+
+        rclone
+            .arg("copy")
+            .arg("--bwlimit")
+            .arg(format!("{}K", self.speed));
+
+        match command.link {
+            true => rclone.arg("--links"),
+            false => rclone.arg("--copy-links"),
+        };
+
+        rclone.arg(&command.local_path).arg(&target);
+
+        let started = Instant::now();
+        let output = rclone.output();
+        let duration = started.elapsed();
+
+        let (exit_code, stderr) = match output {
+            Ok(output) => (output.status.code(), String::from_utf8_lossy(&output.stderr).to_string()),
+            Err(error) => (None, format!("failed to start rclone: {}", error)),
+        };
+
+        let bytes = match exit_code {
+            Some(0) => fs::metadata(&command.local_path).map(|meta| meta.len()).unwrap_or(0),
+            _ => 0,
+        };
+
+        TransferOutcome {
+            cloud: self.cloud,
+            local_path: command.local_path.clone(),
+            target,
+            exit_code,
+            duration,
+            bytes,
+            stderr: TransferOutcome::trim_stderr(&stderr),
+            attempts: 1,
+        }
+    }
+
+    fn target(&self, command: &Command, root: &str) -> String {
+        command.remote_target(&self.remote, root)
+    }
+
+    fn list_snapshots(&self, parent: &str) -> std::io::Result<Vec<String>> {
+        let stdout = self.run(self.rclone()
+            .arg("lsf")
+            .arg("--dirs-only")
+            .arg(format!("{}:{}", self.remote, parent)))?;
+
+        Ok(stdout
+            .lines()
+            .map(|line| line.trim_end_matches('/').to_string())
+            .filter(|name| !name.is_empty())
+            .collect())
+    }
+
+    fn purge(&self, path: &str) -> std::io::Result<()> {
+        self.run(self.rclone()
+            .arg("purge")
+            .arg(format!("{}:{}", self.remote, path)))
+            .map(|_| ())
+    }
+
+    fn list_files(&self, root: &str) -> std::io::Result<Vec<String>> {
+        let stdout = self.run(self.rclone()
+            .arg("lsf")
+            .arg("--recursive")
+            .arg("--files-only")
+            .arg(format!("{}:{}", self.remote, root)))?;
+
+        Ok(stdout
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    fn download(&self, root: &str, remote_file: &str, destination: &Path) -> std::io::Result<()> {
+        self.run(self.rclone()
+            .arg("copyto")
+            .arg("--bwlimit")
+            .arg(format!("{}K", self.speed))
+            .arg(format!("{}:{}/{}", self.remote, root, remote_file))
+            .arg(destination))
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,23 @@
+use std::path::Path;
+
+use crate::models::{command::Command, transfer_outcome::TransferOutcome};
+
+/// Destination that a planned `Command` can be uploaded to.
+pub trait TransferBackend: Send + Sync {
+    fn transfer(&self, command: &Command, root: &str) -> TransferOutcome;
+
+    /// Where `command` would end up, used to display a plan without uploading.
+    fn target(&self, command: &Command, root: &str) -> String;
+
+    /// Names of the folders directly under `parent`.
+    fn list_snapshots(&self, parent: &str) -> std::io::Result<Vec<String>>;
+
+    /// Deletes the folder at `path` and everything in it.
+    fn purge(&self, path: &str) -> std::io::Result<()>;
+
+    /// Every file below `root`, as `/`-separated paths relative to it.
+    fn list_files(&self, root: &str) -> std::io::Result<Vec<String>>;
+
+    /// Copies the file `root/remote_file` back to `destination`.
+    fn download(&self, root: &str, remote_file: &str, destination: &Path) -> std::io::Result<()>;
+}
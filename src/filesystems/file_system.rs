@@ -0,0 +1,28 @@
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::models::{file_id::FileId, file_kind::FileKind, file_metadata::FileMetadata};
+
+/// What the planner reads from disk, so a template can also be planned
+/// against a tree that only exists in memory.
+pub trait FileSystem: Send + Sync {
+    fn exists(&self, path: &Path) -> bool;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// What `path` itself is, a final symlink is not followed.
+    fn kind(&self, path: &Path) -> FileKind;
+
+    /// Identity of what `path` leads to once every symlink is followed.
+    fn id(&self, path: &Path) -> Option<FileId>;
+
+    /// Paths of the files and directories directly inside `path`.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Opens the file at `path` for reading from the start.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+}
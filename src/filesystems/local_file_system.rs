@@ -0,0 +1,74 @@
+use std::{fs::{self, File}, io::{self, Read}, path::{Path, PathBuf}};
+
+use crate::models::{file_id::FileId, file_kind::FileKind, file_metadata::FileMetadata};
+
+use super::file_system::FileSystem;
+
+/// The disk of the machine running the backup.
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn kind(&self, path: &Path) -> FileKind {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return FileKind::Missing;
+        };
+
+        let file_type = metadata.file_type();
+
+        if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_file() {
+            FileKind::File
+        } else {
+            FileKind::Special
+        }
+    }
+
+    #[cfg(unix)]
+    fn id(&self, path: &Path) -> Option<FileId> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path).ok()?;
+
+        Some(FileId::Inode {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn id(&self, path: &Path) -> Option<FileId> {
+        fs::canonicalize(path).ok().map(FileId::Path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(path)?.flatten().map(|entry| entry.path()).collect())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::metadata(path)?;
+
+        Ok(FileMetadata {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::models::{file_id::FileId, file_kind::FileKind, file_metadata::FileMetadata};
+
+use super::file_system::FileSystem;
+
+/// A directory tree held in memory, to plan a template against a tree
+/// recorded on another machine. Parents of every file and directory exist
+/// implicitly and symlink targets are absolute. Reads from JSON as
+///
+/// ```json
+/// {
+///   "dirs": ["/home/me/empty"],
+///   "files": {
+///     "/home/me/notes.txt": { "content": "todo", "modified": 1700000000 },
+///     "/home/me/movie.mkv": { "size": 4294967296 }
+///   },
+///   "links": { "/home/me/latest": "/home/me/notes.txt" },
+///   "special": ["/home/me/.socket"]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(from = "MemoryTree")]
+pub struct MemoryFileSystem {
+    dirs: BTreeSet<PathBuf>,
+    files: BTreeMap<PathBuf, MemoryFile>,
+    links: BTreeMap<PathBuf, PathBuf>,
+    special: BTreeSet<PathBuf>,
+}
+
+/// Links followed while resolving a path before giving up on a loop, like
+/// `ELOOP`.
+const MAX_LINKS: usize = 40;
+
+/// A file of a `MemoryFileSystem`, `size` defaults to the length of
+/// `content` and `modified` is in seconds since the Unix epoch.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryFile {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub modified: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemoryTree {
+    #[serde(default)]
+    dirs: Vec<PathBuf>,
+    #[serde(default)]
+    files: BTreeMap<PathBuf, MemoryFile>,
+    #[serde(default)]
+    links: BTreeMap<PathBuf, PathBuf>,
+    #[serde(default)]
+    special: Vec<PathBuf>,
+}
+
+impl From<MemoryTree> for MemoryFileSystem {
+    fn from(tree: MemoryTree) -> Self {
+        let mut file_system = MemoryFileSystem::default();
+
+        tree.dirs.into_iter().for_each(|dir| file_system.add_dir(dir));
+        tree.files.into_iter().for_each(|(path, file)| file_system.add_file(path, file));
+        tree.links.into_iter().for_each(|(path, target)| file_system.add_link(path, target));
+        tree.special.into_iter().for_each(|path| file_system.add_special(path));
+
+        file_system
+    }
+}
+
+impl MemoryFileSystem {
+    /// A tree holding `files`, each a path and its content.
+    #[cfg(test)]
+    pub fn with_files(files: &[(&str, &str)]) -> Self {
+        let mut file_system = MemoryFileSystem::default();
+
+        for (path, content) in files {
+            file_system.add_file(
+                *path,
+                MemoryFile {
+                    content: content.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        file_system
+    }
+
+    pub fn add_dir(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+
+        self.add_parents(&path);
+        self.dirs.insert(path);
+    }
+
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, file: MemoryFile) {
+        let path = path.into();
+
+        self.add_parents(&path);
+        self.files.insert(path, file);
+    }
+
+    pub fn add_link(&mut self, path: impl Into<PathBuf>, target: impl Into<PathBuf>) {
+        let path = path.into();
+
+        self.add_parents(&path);
+        self.links.insert(path, target.into());
+    }
+
+    pub fn add_special(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+
+        self.add_parents(&path);
+        self.special.insert(path);
+    }
+
+    fn add_parents(&mut self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() || !self.dirs.insert(parent.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    /// `path` with every symlink along it replaced by its target.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let mut path = path.to_path_buf();
+
+        for _ in 0..MAX_LINKS {
+            let Some((link, target)) = path.ancestors().find_map(|link| self.links.get(link).map(|target| (link, target))) else {
+                break;
+            };
+
+            path = match path.strip_prefix(link) {
+                Ok(rest) if !rest.as_os_str().is_empty() => target.join(rest),
+                _ => target.clone(),
+            };
+        }
+
+        path
+    }
+
+    /// `path` with the symlinks along its parent resolved, but not the last one.
+    fn resolve_parent(&self, path: &Path) -> PathBuf {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => self.resolve(parent).join(name),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    fn file(&self, path: &Path) -> io::Result<&MemoryFile> {
+        self.files
+            .get(&self.resolve(path))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} is not a file", path.display())))
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        let path = self.resolve(path);
+
+        self.dirs.contains(&path) || self.files.contains_key(&path) || self.special.contains(&path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(&self.resolve(path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&self.resolve(path))
+    }
+
+    fn kind(&self, path: &Path) -> FileKind {
+        let path = self.resolve_parent(path);
+
+        if self.links.contains_key(&path) {
+            FileKind::Symlink
+        } else if self.special.contains(&path) {
+            FileKind::Special
+        } else if self.dirs.contains(&path) {
+            FileKind::Dir
+        } else if self.files.contains_key(&path) {
+            FileKind::File
+        } else {
+            FileKind::Missing
+        }
+    }
+
+    fn id(&self, path: &Path) -> Option<FileId> {
+        match self.exists(path) {
+            true => Some(FileId::Path(self.resolve(path))),
+            false => None,
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not a directory", path.display())));
+        }
+
+        let resolved = self.resolve(path);
+
+        Ok(self.dirs
+            .iter()
+            .chain(self.files.keys())
+            .chain(self.links.keys())
+            .chain(self.special.iter())
+            .filter(|child| child.parent() == Some(resolved.as_path()))
+            .filter_map(|child| child.file_name().map(|name| path.join(name)))
+            .collect())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.file(path)?.content.as_bytes()))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let file = self.file(path)?;
+
+        Ok(FileMetadata {
+            size: file.size.unwrap_or(file.content.len() as u64),
+            modified: file.modified.map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds)),
+        })
+    }
+}
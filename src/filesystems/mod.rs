@@ -0,0 +1,3 @@
+pub mod file_system;
+pub mod local_file_system;
+pub mod memory_file_system;
\ No newline at end of file
@@ -1,70 +1,399 @@
-use std::{path::PathBuf, error::Error};
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, error::Error};
+#[cfg(target_os = "windows")]
 use std::process::{Command};
-use std::thread::sleep;
-use std::time::Duration;
-use chrono::{Datelike, Local, Timelike, Utc};
+use std::io::IsTerminal;
+use std::thread;
+use std::time::Instant;
+use chrono::Utc;
+#[cfg(target_os = "windows")]
+use chrono::{Datelike, Local, Timelike};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use regex::Regex;
+#[cfg(target_os = "windows")]
 use cron_parser::parse;
-use mappers::{template_to_dir_entry, dir_entry_to_commands};
+use mappers::{dir_entry_to_commands, commands_to_plan, template_to_dir_entry};
 use services::file_service;
+use services::plan_service::{self, PlanFormat};
+use services::{check_service, explain_service, index_service, journal_service, report_service, restore_service, retention_service, template_service, transfer_service};
+use services::template_service::TemplateFormat;
+#[cfg(target_os = "windows")]
 use crate::models::config::Config;
-use crate::models::scheduler::{Scheduler};
+use crate::filesystems::file_system::FileSystem;
+use crate::filesystems::local_file_system::LocalFileSystem;
+use crate::filesystems::memory_file_system::MemoryFileSystem;
+use crate::models::file_index::FileIndex;
+use crate::models::scheduler::{Cloud, Scheduler};
+use crate::models::template_issue::Severity;
+use crate::models::transfer_outcome::TransferOutcome;
+use crate::models::transfer_report::TransferReport;
 
 mod services;
 mod mappers;
 mod models;
+mod backends;
+mod filesystems;
 
 /// Program that convert template to list of rclone commands
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
     /// Path to the scheduler
-    #[arg(short, long)]
-    path: PathBuf,
+    #[arg(short, long, required = true)]
+    path: Option<PathBuf>,
     /// Is first excecution
-    #[arg(short, long)]
+    #[arg(short, long, default_value = "n")]
     first: String,
 
-    #[arg(short, long)]
-    config: PathBuf,
+    #[arg(short, long, required = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Mode {
+    /// Print the ordered list of files the template selects without uploading anything
+    Plan {
+        #[arg(long, value_enum, default_value_t = PlanFormat::Table)]
+        format: PlanFormat,
+        /// Plan against a directory tree recorded as JSON instead of the local disk
+        #[arg(long)]
+        tree: Option<PathBuf>,
+        /// Trace which template rules selected or left out this path instead of printing the plan
+        #[arg(long)]
+        explain: Option<PathBuf>,
+    },
+    /// Copy the files of a snapshot back to disk, an incremental one together
+    /// with the snapshots it builds on
+    Restore {
+        #[arg(long, value_enum)]
+        cloud: Cloud,
+        /// Snapshot folder name, e.g. 2024_01_31_and_22h_00m_00s, or "latest"
+        #[arg(long, default_value = restore_service::LATEST)]
+        snapshot: String,
+        /// Only restore files whose original path matches this regex
+        #[arg(long)]
+        filter: Option<String>,
+        /// Restore below this directory instead of the original locations
+        #[arg(long)]
+        target: Option<PathBuf>,
+    },
+    /// Parse a template and report every problem without running a backup
+    Check {
+        /// Template to check, defaults to the template of the scheduler given with --path
+        #[arg(long)]
+        template: Option<PathBuf>,
+        /// Also fail on warnings
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Translate a template between the line format and the structured formats,
+    /// line templates using `include`, `set` or `${NAME}` only convert to lines
+    Convert {
+        /// Template to read, its format is picked by extension
+        #[arg(long)]
+        input: PathBuf,
+        /// File to write, printed to stdout when missing
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Output format, defaults to the format of the output extension
+        #[arg(long, value_enum)]
+        to: Option<TemplateFormat>,
+    },
+    /// Delete the snapshots that fall outside the scheduler's retention policy
+    Prune {
+        /// Only print the snapshots that would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
+    match &args.mode {
+        Some(Mode::Check { template, strict }) => return check(args.path.as_deref(), template.as_deref(), *strict),
+        Some(Mode::Convert { input, output, to }) => return convert(input, output.as_deref(), *to),
+        _ => {}
+    }
+
+    let path = args.path.ok_or("--path is required")?;
+    let config = args.config.ok_or("--config is required")?;
+
+    let scheduler_json = file_service::read_file(&path)?;
+    let scheduler: Scheduler = serde_json::from_str(&scheduler_json)?;
+
+    match args.mode {
+        Some(Mode::Plan { format, tree, explain }) => {
+            let full = scheduler.incremental.is_full_run(&index_service::load(&index_service::index_path(&path)));
+
+            return plan(&scheduler, &config, format, full, tree.as_deref(), explain.as_deref());
+        }
+        Some(Mode::Prune { dry_run }) => return prune(&scheduler, &config, dry_run || scheduler.retention.dry_run),
+        Some(Mode::Restore { cloud, snapshot, filter, target }) => {
+            return restore(&scheduler, &config, cloud, &snapshot, filter, target);
+        }
+        _ => {}
+    }
+
     #[cfg(target_os = "windows")]
     {
         hide_console_window();
     }
 
-    let scheduler_json = file_service::read_file(&args.path)?;
-    let scheduler: Scheduler = serde_json::from_str(&scheduler_json)?;
-
     #[cfg(target_os = "windows")]
     {
-        enable_schtask(&scheduler.name, &scheduler.cron, &args.path.to_string_lossy(), &args.config);
+        enable_schtask(&scheduler.name, &scheduler.cron, &path.to_string_lossy(), &config);
     }
 
     if args.first.to_lowercase() == "y" {
         return Ok(());
     }
 
-    let content = file_service::read_file(&PathBuf::from(scheduler.root))?;
+    let started = Instant::now();
+    let backends = scheduler.clouds.keys()
+        .map(|cloud| cloud.backend(&config, scheduler.speed).map(|backend| (*cloud, backend)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut commands = scheduler_commands(&scheduler, &LocalFileSystem)?;
+    let index_path = index_service::index_path(&path);
+    let mut index = index_service::load(&index_path);
+    let full_run = scheduler.incremental.is_full_run(&index);
+
+    let mut states = HashMap::new();
+
+    if scheduler.incremental.enabled {
+        let empty = FileIndex::default();
+
+        states = index_service::changed_states(&commands, if full_run { &empty } else { &index }, scheduler.incremental.hash);
+        commands.retain(|command| states.contains_key(&command.local_path));
+    }
+
+    let journal_path = journal_service::journal_path(&path);
+    let (journal, writer) = journal_service::open(&journal_path, scheduler.resume_window_minutes, scheduler.snapshot_root(Utc::now(), full_run))?;
+    let mut report = TransferReport::default();
+
+    let on_success = |outcome: &TransferOutcome| {
+        if let Err(error) = writer.record(outcome.cloud, &outcome.local_path, &outcome.target) {
+            eprintln!("Failed to write run journal: {}", error);
+        }
+    };
+
+    let reports = thread::scope(|scope| {
+        let handles = backends.into_iter()
+            .map(|(cloud, backend)| {
+                let pending = commands.iter()
+                    .filter(|command| !journal.is_completed(&cloud, &command.local_path, &backend.target(command, &journal.root)))
+                    .cloned()
+                    .collect::<Vec<models::command::Command>>();
+                let (root, scheduler, on_success) = (&journal.root, &scheduler, &on_success);
+
+                scope.spawn(move || {
+                    transfer_service::run(backend.as_ref(), &pending, root, &scheduler.retry, scheduler.concurrency, on_success)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter()
+            .map(|handle| handle.join().expect("Transfer worker panicked"))
+            .collect::<Vec<TransferReport>>()
+    });
+
+    reports.into_iter().for_each(|cloud_report| report.extend(cloud_report));
+
+    if scheduler.incremental.enabled {
+        let gave_up = report.gave_up.iter().map(|outcome| &outcome.local_path).collect::<HashSet<&String>>();
+
+        for (path, state) in states.iter() {
+            if !gave_up.contains(path) {
+                index.files.insert(path.clone(), state.clone());
+            }
+        }
+
+        index.runs_since_full = if full_run { 0 } else { index.runs_since_full + 1 };
+        index_service::save(&index_path, &index)?;
+    }
+
+    print!("{}", report_service::summary(&report, started.elapsed()));
+
+    if !report.is_success() {
+        std::process::exit(1);
+    }
+
+    journal_service::finish(&journal_path)?;
+
+    prune(&scheduler, &config, scheduler.retention.dry_run)?;
+
+    Ok(())
+}
+
+fn scheduler_commands(scheduler: &Scheduler, fs: &dyn FileSystem) -> Result<Vec<models::command::Command>, Box<dyn Error>> {
+    let template = PathBuf::from(&scheduler.root);
+    let content = file_service::read_file(&template)?;
+
+    let entries = template_service::map(content, TemplateFormat::from_path(&template), Some(&template))
+        .map_err(|error| format!("{}: {}", scheduler.root, error))?;
+
+    Ok(dir_entry_to_commands::map(entries, fs, &scheduler.content, scheduler.links, scheduler.scan_threads, &scan_progress)?)
+}
+
+/// Shows how far the template walk got when stderr is a terminal, so logs
+/// and piped plans stay clean. Clears the line once nothing is queued.
+fn scan_progress(visited: usize, pending: usize) {
+    if !std::io::stderr().is_terminal() {
+        return;
+    }
+
+    match pending {
+        0 => eprint!("\r\x1b[2K"),
+        _ => eprint!("\rscanned {} entries, {} queued", visited, pending),
+    }
+}
+
+fn check(scheduler_path: Option<&Path>, template: Option<&Path>, strict: bool) -> Result<(), Box<dyn Error>> {
+    let template = match (template, scheduler_path) {
+        (Some(template), _) => template.to_path_buf(),
+        (None, Some(scheduler_path)) => {
+            let scheduler: Scheduler = serde_json::from_str(&file_service::read_file(scheduler_path)?)?;
+            PathBuf::from(scheduler.root)
+        }
+        (None, None) => return Err("check needs --template or --path".into()),
+    };
+
+    let issues = check_service::check(&file_service::read_file(&template)?, TemplateFormat::from_path(&template), Some(&template), &LocalFileSystem);
+
+    for issue in issues.iter() {
+        println!("{}:{}", template.display(), issue);
+    }
+
+    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+    let warnings = issues.len() - errors;
+
+    println!("{} error(s), {} warning(s)", errors, warnings);
+
+    if errors > 0 || (strict && warnings > 0) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn convert(input: &Path, output: Option<&Path>, to: Option<TemplateFormat>) -> Result<(), Box<dyn Error>> {
+    let to = match (to, output) {
+        (Some(to), _) => to,
+        (None, Some(output)) => TemplateFormat::from_path(output),
+        (None, None) => return Err("convert needs --to or --output".into()),
+    };
+
+    let content = file_service::read_file(input)?;
+    let from = TemplateFormat::from_path(input);
+
+    if from == TemplateFormat::Lines && to != TemplateFormat::Lines && template_to_dir_entry::has_directives(&content) {
+        return Err(format!("{} uses include, set or ${{NAME}}, which {:?} templates do not support", input.display(), to).into());
+    }
+
+    let entries = template_service::map(content, from, Some(input)).map_err(|error| format!("{}: {}", input.display(), error))?;
+
+    let rendered = template_service::render(&entries, to)?;
+
+    // Parse the output again so a lossy conversion fails instead of silently changing rules.
+    let reparsed = template_service::map(rendered.clone(), to, None)?;
+
+    if !template_service::same_entries(&entries, &reparsed) {
+        return Err(format!("{} cannot be converted to {:?} without changing its rules", input.display(), to).into());
+    }
+
+    match output {
+        Some(output) => std::fs::write(output, rendered)?,
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+fn restore(
+    scheduler: &Scheduler,
+    config: &Path,
+    cloud: Cloud,
+    snapshot: &str,
+    filter: Option<String>,
+    target: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let backend = cloud.backend(config, scheduler.speed)?;
+    let parent = scheduler.snapshot_parent();
+    let filter = filter.map(|filter| Regex::new(&filter)).transpose()?;
+
+    let roots = restore_service::resolve_snapshots(backend.as_ref(), &parent, snapshot, scheduler.incremental.enabled)?
+        .into_iter()
+        .map(|snapshot| format!("{}/{}", parent, snapshot))
+        .collect::<Vec<String>>();
+
+    for root in &roots {
+        println!("Restoring [{:?}] {}", cloud, root);
+    }
+
+    let report = restore_service::restore(cloud, backend.as_ref(), &roots, filter.as_ref(), target.as_deref())?;
+
+    print!("{}", report_service::summary(&report, started.elapsed()));
+
+    if !report.is_success() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn prune(scheduler: &Scheduler, config: &Path, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let parent = scheduler.snapshot_parent();
+    let now = Utc::now().naive_utc();
+
+    for cloud in scheduler.clouds.keys() {
+        let backend = cloud.backend(config, scheduler.speed)?;
 
-    let entries = template_to_dir_entry::map(content)?;
+        for path in retention_service::prune(backend.as_ref(), &parent, &scheduler.retention, scheduler.incremental.enabled, now, dry_run)? {
+            match dry_run {
+                true => println!("Would prune [{:?}] {}", cloud, path),
+                false => println!("Pruned [{:?}] {}", cloud, path),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `full` tells whether the next run uploads every file or only the changed ones.
+fn plan(scheduler: &Scheduler, config: &Path, format: PlanFormat, full: bool, tree: Option<&Path>, explain: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let tree = match tree {
+        Some(tree) => Some(serde_json::from_str::<MemoryFileSystem>(&file_service::read_file(tree)?)?),
+        None => None,
+    };
+    let fs: &dyn FileSystem = match tree.as_ref() {
+        Some(tree) => tree,
+        None => &LocalFileSystem,
+    };
+
+    if let Some(path) = explain {
+        let template = PathBuf::from(&scheduler.root);
+        let content = file_service::read_file(&template)?;
 
-    let commands = dir_entry_to_commands::map(entries)?;
-    let hostname = gethostname::gethostname().to_string_lossy().to_string();
-    let root = format!("{}/{}/{}", hostname, scheduler.name, Utc::now().format("%Y_%m_%d_and_%Hh_%Mm_%Ss"));
+        let report = explain_service::explain(&content, TemplateFormat::from_path(&template), Some(&template), scheduler, fs, path)
+            .map_err(|error| format!("{}: {}", scheduler.root, error))?;
 
-    for (cloud, _) in scheduler.clouds {
-        commands.iter()
-            .for_each(|command| {
-                command.spawn_rclone_command(&cloud.name(&args.config), &root, scheduler.speed);
-                sleep(Duration::from_millis(50));
-            });
+        print!("{}", report);
+
+        return Ok(());
     }
+
+    let commands = scheduler_commands(scheduler, fs)?;
+    let root = scheduler.snapshot_root(Utc::now(), full);
+    let clouds = scheduler.clouds.keys().copied().collect::<Vec<Cloud>>();
+
+    let plan = commands_to_plan::map(&commands, &clouds, config, scheduler.speed, &root)?;
+
+    print!("{}", plan_service::render(&plan, format)?);
+
     Ok(())
 }
 
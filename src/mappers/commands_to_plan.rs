@@ -0,0 +1,22 @@
+use std::path::Path;
+
+use crate::models::{command::Command, plan_entry::PlanEntry, scheduler::Cloud};
+
+pub fn map(commands: &[Command], clouds: &[Cloud], config_path: &Path, speed: f32, root: &str) -> Result<Vec<PlanEntry>, String> {
+    let mut plan = Vec::new();
+
+    for cloud in clouds {
+        let backend = cloud.backend(config_path, speed)?;
+
+        for command in commands {
+            plan.push(PlanEntry {
+                cloud: *cloud,
+                local_path: command.local_path.clone(),
+                remote_path: backend.target(command, root),
+                priority: command.priority,
+            });
+        }
+    }
+
+    Ok(plan)
+}
@@ -1,251 +1,478 @@
-use std::{collections::HashSet, fs, path::Path};
+use std::{collections::HashSet, path::Path};
 use std::path::PathBuf;
-
-use regex::Regex;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::{
-    models::{command::Command, dir_entry::DirEntry, entry_file_filter::EntryFileFilter},
-    services::file_service,
+    filesystems::file_system::FileSystem,
+    models::{command::Command, content_policy::ContentPolicy, dir_entry::DirEntry, entry_file_filter::EntryFileFilter, link_policy::LinkPolicy},
+    services::content_service,
 };
 use crate::models::entry_dir_file_priority::EntryDirFilePriority;
 use crate::models::entry_dir_priority::EntryDirPriority;
+use crate::models::entry_exclude::EntryExclude;
 use crate::models::entry_file_priority::EntryFilePriority;
+use crate::models::explain_step::ExplainStep;
+use crate::models::file_kind::FileKind;
+use crate::models::file_predicate::FilePredicate;
+use crate::models::pattern::Pattern;
+
+/// What every step of the walk reads files with.
+struct Context<'a> {
+    fs: &'a dyn FileSystem,
+    content: &'a ContentPolicy,
+    links: LinkPolicy,
+    /// Paths written in the template, followed whatever `links` says.
+    roots: HashSet<PathBuf>,
+}
+
+/// Entries left to visit, shared by the walker threads.
+struct Walk {
+    pending: Vec<DirEntry>,
+    /// Entries being visited, which may still push more.
+    active: usize,
+    visited: usize,
+}
 
-pub fn map(mut entries: Vec<DirEntry>) -> Result<Vec<Command>, String> {
-    delete_not_exist_entries(&mut entries);
+/// Takes an entry off `active` when dropped, even when visiting it panics,
+/// and wakes the other walkers so they do not wait for it forever.
+struct Visiting<'a> {
+    walk: &'a Mutex<Walk>,
+    changed: &'a Condvar,
+}
 
-    add_file_filter(&mut entries);
+impl Drop for Visiting<'_> {
+    fn drop(&mut self) {
+        self.walk.lock().unwrap_or_else(PoisonError::into_inner).active -= 1;
+        self.changed.notify_all();
+    }
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Walks the tree below `entries` through `fs`, usually the local disk, on
+/// `threads` threads, treating the symlinks it meets as `links` says.
+/// `on_progress` gets the number of entries visited and still queued every
+/// `PROGRESS_INTERVAL` and once the walk is done.
+pub fn map(
+    entries: Vec<DirEntry>,
+    fs: &dyn FileSystem,
+    content: &ContentPolicy,
+    links: LinkPolicy,
+    threads: usize,
+    on_progress: &(dyn Fn(usize, usize) + Sync),
+) -> Result<Vec<Command>, String> {
+    let context = Context {
+        fs,
+        content,
+        links,
+        roots: entries.iter().map(|entry| entry.path.clone()).collect(),
+    };
+
+    Ok(plan(&context, entries, threads, on_progress))
+}
 
-    let mut commands: HashSet<Command> = HashSet::new();
+/// Explains how the template treats `path`. The template entries at or
+/// above it go through the same preparation as in `map`, so they carry the
+/// rules they inherit, and are evaluated against `path` alone with the rule
+/// checks of the planner. A plan on `threads` threads then tells whether
+/// `path` gets a command.
+pub fn explain(entries: Vec<DirEntry>, fs: &dyn FileSystem, content: &ContentPolicy, links: LinkPolicy, threads: usize, path: &Path) -> Vec<ExplainStep> {
+    let context = Context {
+        fs,
+        content,
+        links,
+        roots: entries.iter().map(|entry| entry.path.clone()).collect(),
+    };
+
+    let mut steps = vec![];
+    let mut prepared = entries.iter().filter(|entry| path.starts_with(&entry.path)).cloned().collect::<Vec<DirEntry>>();
+
+    for (prepare, reason) in [
+        (delete_not_exist_entries as fn(&Context, &mut Vec<DirEntry>), "it does not exist"),
+        (add_excludes, "an exclude of a parent entry matches it"),
+        (add_file_filter, "it only has filters and excludes, they apply to the entries below it"),
+    ] {
+        let before = prepared.iter().map(|entry| entry.path.clone()).collect::<Vec<PathBuf>>();
+
+        prepare(&context, &mut prepared);
+
+        for dropped in before.iter().filter(|path| !prepared.iter().any(|entry| &entry.path == *path)) {
+            steps.push(ExplainStep {
+                path: dropped.clone(),
+                message: format!("template entry left out, {}", reason),
+            });
+        }
+    }
+
+    for entry in prepared.iter() {
+        explain_entry(&context, entry, path, &mut steps);
+    }
 
-    get_commands(&mut entries, &mut commands);
+    let commands = plan(&context, entries, threads, &|_, _| {});
+
+    steps.push(ExplainStep {
+        path: path.to_path_buf(),
+        message: match commands.iter().find(|command| Path::new(&command.local_path) == path) {
+            Some(Command { priority: Some(priority), .. }) => format!("planned with priority {}", priority),
+            Some(_) => "planned without priority, after every prioritized file".to_string(),
+            None => "not planned".to_string(),
+        },
+    });
+
+    steps
+}
+
+fn plan(context: &Context, mut entries: Vec<DirEntry>, threads: usize, on_progress: &(dyn Fn(usize, usize) + Sync)) -> Vec<Command> {
+    delete_not_exist_entries(context, &mut entries);
+
+    add_excludes(context, &mut entries);
+
+    add_file_filter(context, &mut entries);
+
+    let commands = get_commands(context, entries, threads, on_progress);
 
     let mut commands: Vec<Command> = commands.into_iter().collect();
 
     commands.sort_by_key(|cmd| cmd.priority.unwrap_or(usize::MAX));
 
     for cmd in &mut commands {
-        let mut path = PathBuf::from(cmd.local_path.clone().replace(":", ""));
-        path.pop();
-        cmd.remote_path = path.to_string_lossy().to_string();
+        cmd.remote_path = remote_path(&cmd.local_path);
     }
 
+    commands
+}
+
+/// Remote folder of a local file: the parent directory with the `:` of the
+/// drive letter stripped, `C:\Users\a.txt` -> `C\Users`.
+pub fn remote_path(local_path: &str) -> String {
+    let mut path = PathBuf::from(local_path.replace(":", ""));
+    path.pop();
+    path.to_string_lossy().to_string()
+}
 
+/// Reverses `remote_path` for a file below a snapshot root, given as its
+/// `/`-separated path relative to that root: `C/Users/a.txt` -> `C:\Users\a.txt`
+/// on Windows, `home/a.txt` -> `/home/a.txt` elsewhere.
+pub fn local_path(remote_file: &str) -> PathBuf {
+    let segments = remote_file
+        .split(['/', '\\'])
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<&str>>();
+
+    let mut path = PathBuf::new();
+
+    if cfg!(target_os = "windows") {
+        if let Some((drive, rest)) = segments.split_first() {
+            path.push(format!("{}:\\", drive));
+            rest.iter().for_each(|segment| path.push(segment));
+        }
+    } else {
+        path.push("/");
+        segments.iter().for_each(|segment| path.push(segment));
+    }
 
-    Ok(commands)
+    path
 }
 
-fn get_commands(entries: &mut Vec<DirEntry>, set: &mut HashSet<Command>) {
-    while !entries.is_empty() {
-        let mut entry = entries.pop().unwrap();
+/// Walks the entries on `threads` workers sharing one stack. Each worker
+/// visits an entry, pushes what it found below it and merges its commands
+/// through `insert_command`, so the result does not depend on the order
+/// entries are visited in. A panic while visiting reaches the caller once
+/// the other workers are done.
+fn get_commands(context: &Context, entries: Vec<DirEntry>, threads: usize, on_progress: &(dyn Fn(usize, usize) + Sync)) -> HashSet<Command> {
+    let walk = Mutex::new(Walk {
+        pending: entries,
+        active: 0,
+        visited: 0,
+    });
+    let changed = Condvar::new();
+    let commands = Mutex::new(HashSet::new());
+    let reported = Mutex::new(Instant::now());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let entry = {
+                    let mut walk = walk.lock().unwrap();
+
+                    loop {
+                        if let Some(entry) = walk.pending.pop() {
+                            walk.active += 1;
+                            break entry;
+                        }
 
-        if entry.is_file() {
-            if let Some(command) = get_file_command(&mut entry) {
-                insert_command(set, command);
-            }
-            continue;
-        }
+                        if walk.active == 0 {
+                            return;
+                        }
 
-        let file_priorities = if let Some(priorities) = entry.entry_dir_file_priority.clone() {
-            let mut new_priorities = vec![];
-            for priority in priorities {
-                if priority.deep == None {
-                    new_priorities.push(priority);
-                    continue;
+                        walk = changed.wait(walk).unwrap();
+                    }
+                };
+
+                let visiting = Visiting { walk: &walk, changed: &changed };
+                let mut found = vec![];
+                let mut found_commands = vec![];
+
+                visit(context, entry, &mut found, &mut found_commands);
+
+                if !found_commands.is_empty() {
+                    let mut commands = commands.lock().unwrap();
+                    found_commands.into_iter().for_each(|command| insert_command(&mut commands, command));
                 }
 
-                if priority.deep.unwrap() == -1 {
-                    continue;
+                {
+                    let mut walk = walk.lock().unwrap();
+                    walk.pending.extend(found);
+                    walk.visited += 1;
+
+                    let mut reported = reported.lock().unwrap();
+
+                    if reported.elapsed() >= PROGRESS_INTERVAL {
+                        *reported = Instant::now();
+                        on_progress(walk.visited, walk.pending.len());
+                    }
                 }
 
-                new_priorities.push(EntryDirFilePriority {
-                    regex: priority.regex,
-                    content: priority.content,
-                    priority: priority.priority,
-                    deep: Some(priority.deep.unwrap() - 1),
-                    root: priority.root,
-                })
+                drop(visiting);
+            });
+        }
+    });
+
+    on_progress(walk.into_inner().unwrap().visited, 0);
+
+    commands.into_inner().unwrap()
+}
+
+fn visit(context: &Context, mut entry: DirEntry, entries: &mut Vec<DirEntry>, commands: &mut Vec<Command>) {
+    match walk_kind(context, &entry.path) {
+        Some(FileKind::Dir) if enters(context, &entry.path) => {}
+        Some(FileKind::Dir) | None => return,
+        Some(_) => {
+            if let Some(command) = get_file_command(context, &mut entry) {
+                commands.push(command);
             }
+            return;
+        }
+    }
 
-            Some(new_priorities)
-        } else {
-            None
-        };
+    let file_priorities = if let Some(priorities) = entry.entry_dir_file_priority.clone() {
+        let mut new_priorities = vec![];
+        for priority in priorities {
+            if priority.deep.is_none() {
+                new_priorities.push(priority);
+                continue;
+            }
 
-        let dir_priority = if let Some(priorities) = entry.entry_dir_priority.clone() {
-            let mut new_priorities = vec![];
+            if priority.deep.unwrap() == -1 {
+                continue;
+            }
 
-            for priority in priorities {
-                if priority.deep == None {
-                    new_priorities.push(priority);
-                    continue;
-                }
+            new_priorities.push(EntryDirFilePriority {
+                regex: priority.regex,
+                content: priority.content,
+                predicates: priority.predicates,
+                priority: priority.priority,
+                deep: Some(priority.deep.unwrap() - 1),
+                root: priority.root,
+            })
+        }
 
-                if priority.deep.unwrap() == -1 {
-                    continue;
-                }
+        Some(new_priorities)
+    } else {
+        None
+    };
+
+    let dir_priority = if let Some(priorities) = entry.entry_dir_priority.clone() {
+        let mut new_priorities = vec![];
 
-                new_priorities.push(EntryDirPriority {
-                    regex: priority.regex,
-                    priority: priority.priority,
-                    deep: Some(priority.deep.unwrap() - 1),
-                    root: priority.root,
-                })
+        for priority in priorities {
+            if priority.deep.is_none() {
+                new_priorities.push(priority);
+                continue;
             }
 
-            Some(new_priorities)
-        } else {
-            None
-        };
+            if priority.deep.unwrap() == -1 {
+                continue;
+            }
 
-        let file_filter = if let Some(filters) = entry.entry_file_filter.clone() {
-            let mut new_filters = vec![];
+            new_priorities.push(EntryDirPriority {
+                regex: priority.regex,
+                priority: priority.priority,
+                deep: Some(priority.deep.unwrap() - 1),
+                root: priority.root,
+            })
+        }
 
-            for filter in filters {
-                if filter.deep == None {
-                    new_filters.push(filter);
-                    continue;
-                }
+        Some(new_priorities)
+    } else {
+        None
+    };
 
-                if filter.deep.unwrap() == -1 {
-                    continue;
-                }
+    let file_filter = if let Some(filters) = entry.entry_file_filter.clone() {
+        let mut new_filters = vec![];
 
-                new_filters.push(EntryFileFilter {
-                    regex: filter.regex.to_owned(),
-                    content: filter.content.to_owned(),
-                    deep: Some(filter.deep.unwrap() - 1),
-                })
+        for filter in filters {
+            if filter.deep.is_none() {
+                new_filters.push(filter);
+                continue;
             }
 
-            Some(new_filters)
-        } else {
-            None
-        };
+            if filter.deep.unwrap() == -1 {
+                continue;
+            }
 
-        if entry.is_just_selected() {
-            if let Ok(new_entries) = fs::read_dir(&entry.path) {
-                for entry in new_entries {
-                    if let Ok(entry) = entry {
-                        let path = entry.path();
-                        let mut entry = DirEntry::default();
-                        entry.path = path;
-                        entry.selected = true;
-
-                        if entry.is_file() {
-                            if let Some(filters) = file_filter.as_ref() {
-                                if !compare_file_by_filters(&entry, filters) {
-                                    continue;
-                                }
-                            }
+            new_filters.push(EntryFileFilter {
+                regex: filter.regex.to_owned(),
+                content: filter.content.to_owned(),
+                predicates: filter.predicates.to_owned(),
+                deep: Some(filter.deep.unwrap() - 1),
+                root: filter.root,
+            })
+        }
 
-                            entries.push(entry)
+        Some(new_filters)
+    } else {
+        None
+    };
+
+    let excludes = entry.entry_exclude.as_ref().map(|excludes| next_level_excludes(excludes));
+
+    if entry.is_just_selected() {
+        if let Ok(new_entries) = context.fs.read_dir(&entry.path) {
+            for path in new_entries {
+                let entry = DirEntry {
+                    path,
+                    selected: true,
+                    ..Default::default()
+                };
+
+                if is_excluded(&entry.path, excludes.as_deref()) {
+                    continue;
+                }
+
+                if walk_kind(context, &entry.path) == Some(FileKind::File) {
+                    if let Some(filters) = file_filter.as_ref() {
+                        if !compare_file_by_filters(context, &entry, filters) {
+                            continue;
                         }
                     }
+
+                    entries.push(entry)
                 }
             }
-            continue;
         }
+        return;
+    }
 
-        if let Ok(new_entries) = fs::read_dir(&entry.path) {
-            for entry in new_entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    let mut entry = DirEntry::default();
-                    entry.path = path;
-
-                    match entry.is_dir() {
-                        true => {
-                            entry.entry_file_filter = file_filter.clone();
-
-
-                            if let Some(priorities) = dir_priority.as_ref() {
-                                let mut new_dir_priorities = vec![];
-
-                                for dir_priority in priorities {
-                                    if compare_entry_name_by_regex(&entry.short_name(), &dir_priority.regex) {
-                                        if let Ok(dir_entries) = fs::read_dir(&entry.path) {
-                                            for dir_entry in dir_entries {
-                                                if let Ok(dir_entry) = dir_entry {
-                                                    let path = dir_entry.path();
-                                                    let mut dir_entry = DirEntry::default();
-                                                    dir_entry.path = path;
-
-                                                    if dir_entry.is_file() {
-                                                        dir_entry.entry_file_priority = Some(vec![EntryFilePriority {
-                                                            content: "".to_string(),
-                                                            priority: dir_priority.priority,
-                                                            root: dir_priority.root.to_owned(),
-                                                        }]);
-                                                        entries.push(dir_entry);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
+    if let Ok(new_entries) = context.fs.read_dir(&entry.path) {
+        for path in new_entries {
+            let mut entry = DirEntry {
+                path,
+                ..Default::default()
+            };
 
-                                    new_dir_priorities.push(EntryDirPriority {
-                                        regex: dir_priority.regex.to_owned(),
-                                        deep: dir_priority.deep,
-                                        priority: dir_priority.priority,
-                                        root: dir_priority.root.to_owned(),
-                                    })
-                                }
+            if is_excluded(&entry.path, excludes.as_deref()) {
+                continue;
+            }
 
-                                if !new_dir_priorities.is_empty() {
-                                    entry.entry_dir_priority = Some(new_dir_priorities);
+            match walk_kind(context, &entry.path) {
+                None => continue,
+                Some(FileKind::Dir) => {
+                    entry.entry_file_filter = file_filter.clone();
+                    entry.entry_exclude = excludes.clone();
+
+
+                    if let Some(priorities) = dir_priority.as_ref() {
+                        let mut new_dir_priorities = vec![];
+
+                        for dir_priority in priorities {
+                            if compare_entry_by_pattern(&entry.path, &dir_priority.root, &dir_priority.regex) {
+                                let dir_excludes = excludes.as_ref().map(|excludes| next_level_excludes(excludes));
+
+                                if let Ok(dir_entries) = context.fs.read_dir(&entry.path) {
+                                    for path in dir_entries {
+                                        let mut dir_entry = DirEntry {
+                                            path,
+                                            ..Default::default()
+                                        };
+
+                                        if walk_kind(context, &dir_entry.path) == Some(FileKind::File) && !is_excluded(&dir_entry.path, dir_excludes.as_deref()) {
+                                            dir_entry.entry_file_priority = Some(vec![EntryFilePriority {
+                                                content: Pattern::default(),
+                                                predicates: vec![],
+                                                priority: dir_priority.priority,
+                                                root: dir_priority.root.to_owned(),
+                                            }]);
+                                            entries.push(dir_entry);
+                                        }
+                                    }
                                 }
                             }
 
-                            if let Some(priorities) = file_priorities.as_ref() {
-                                let mut new_dir_file_priorities = vec![];
-
-                                for dir_file_priority in priorities {
-                                    new_dir_file_priorities.push(EntryDirFilePriority {
-                                        regex: dir_file_priority.regex.to_owned(),
-                                        content: dir_file_priority.content.to_owned(),
-                                        priority: dir_file_priority.priority,
-                                        deep: dir_file_priority.deep,
-                                        root: dir_file_priority.root.to_owned(),
-                                    })
-                                }
+                            new_dir_priorities.push(EntryDirPriority {
+                                regex: dir_priority.regex.to_owned(),
+                                deep: dir_priority.deep,
+                                priority: dir_priority.priority,
+                                root: dir_priority.root.to_owned(),
+                            })
+                        }
 
-                                if !new_dir_file_priorities.is_empty() {
-                                    entry.entry_dir_file_priority = Some(new_dir_file_priorities);
-                                }
-                            }
+                        if !new_dir_priorities.is_empty() {
+                            entry.entry_dir_priority = Some(new_dir_priorities);
+                        }
+                    }
 
-                            if entry.entry_dir_priority != None || entry.entry_dir_file_priority != None {
-                                entries.push(entry);
-                            }
+                    if let Some(priorities) = file_priorities.as_ref() {
+                        let mut new_dir_file_priorities = vec![];
+
+                        for dir_file_priority in priorities {
+                            new_dir_file_priorities.push(EntryDirFilePriority {
+                                regex: dir_file_priority.regex.to_owned(),
+                                content: dir_file_priority.content.to_owned(),
+                                predicates: dir_file_priority.predicates.to_owned(),
+                                priority: dir_file_priority.priority,
+                                deep: dir_file_priority.deep,
+                                root: dir_file_priority.root.to_owned(),
+                            })
                         }
-                        false => {
-                            if let Some(filters) = file_filter.as_ref() {
-                                if !compare_file_by_filters(&entry, filters) {
-                                    continue;
-                                }
-                            }
 
-                            if let Some(priorities) = file_priorities.as_ref() {
-                                let mut new_file_priorities = vec![];
+                        if !new_dir_file_priorities.is_empty() {
+                            entry.entry_dir_file_priority = Some(new_dir_file_priorities);
+                        }
+                    }
 
-                                for file_priority in priorities {
-                                    if !compare_entry_name_by_regex(&entry.short_name(), &file_priority.regex) {
-                                        continue;
-                                    }
+                    if entry.entry_dir_priority.is_some() || entry.entry_dir_file_priority.is_some() {
+                        entries.push(entry);
+                    }
+                }
+                Some(_) => {
+                    if let Some(filters) = file_filter.as_ref() {
+                        if !compare_file_by_filters(context, &entry, filters) {
+                            continue;
+                        }
+                    }
 
-                                    new_file_priorities.push(EntryFilePriority {
-                                        content: file_priority.content.to_owned(),
-                                        priority: file_priority.priority,
-                                        root: file_priority.root.to_owned(),
-                                    })
-                                }
+                    if let Some(priorities) = file_priorities.as_ref() {
+                        let mut new_file_priorities = vec![];
 
-                                if !new_file_priorities.is_empty() {
-                                    entry.entry_file_priority = Some(new_file_priorities);
-                                    entries.push(entry);
-                                }
+                        for file_priority in priorities {
+                            if !compare_entry_by_pattern(&entry.path, &file_priority.root, &file_priority.regex) {
+                                continue;
                             }
+
+                            new_file_priorities.push(EntryFilePriority {
+                                content: file_priority.content.to_owned(),
+                                predicates: file_priority.predicates.to_owned(),
+                                priority: file_priority.priority,
+                                root: file_priority.root.to_owned(),
+                            })
+                        }
+
+                        if !new_file_priorities.is_empty() {
+                            entry.entry_file_priority = Some(new_file_priorities);
+                            entries.push(entry);
                         }
                     }
                 }
@@ -254,64 +481,98 @@ fn get_commands(entries: &mut Vec<DirEntry>, set: &mut HashSet<Command>) {
     }
 }
 
+/// How the walk treats `path`: as a `File`, a `Dir`, or not at all. Special
+/// files and broken links are left out, a recorded link is a file.
+fn walk_kind(context: &Context, path: &Path) -> Option<FileKind> {
+    match context.fs.kind(path) {
+        FileKind::File => Some(FileKind::File),
+        FileKind::Dir => Some(FileKind::Dir),
+        FileKind::Symlink if context.links == LinkPolicy::Follow || context.roots.contains(path) => {
+            if context.fs.is_dir(path) {
+                Some(FileKind::Dir)
+            } else if context.fs.is_file(path) {
+                Some(FileKind::File)
+            } else {
+                None
+            }
+        }
+        FileKind::Symlink if context.links == LinkPolicy::Record => Some(FileKind::File),
+        FileKind::Symlink | FileKind::Special | FileKind::Missing => None,
+    }
+}
+
+/// Whether the walk may enter the directory at `path`. A directory reached
+/// through a link is not entered when it is one of the link's own ancestors,
+/// compared by device and inode, which would loop forever.
+fn enters(context: &Context, path: &Path) -> bool {
+    if context.fs.kind(path) != FileKind::Symlink || context.roots.contains(path) {
+        return true;
+    }
 
-fn get_file_command(entry: &mut DirEntry) -> Option<Command> {
-    if !entry.selected && entry.entry_file_priority == None {
+    let Some(id) = context.fs.id(path) else {
+        return false;
+    };
+
+    !path.ancestors().skip(1).any(|ancestor| context.fs.id(ancestor).as_ref() == Some(&id))
+}
+
+/// Whether `path` is a link the walk met and records as itself.
+fn is_recorded_link(context: &Context, path: &Path) -> bool {
+    context.links == LinkPolicy::Record && context.fs.kind(path) == FileKind::Symlink && !context.roots.contains(path)
+}
+
+fn get_file_command(context: &Context, entry: &mut DirEntry) -> Option<Command> {
+    if !entry.selected && entry.entry_file_priority.is_none() {
         return None;
     }
 
-    let priority = get_file_priority_by_file_priority(entry);
+    let priority = get_file_priority_by_file_priority(context, entry);
     Some(Command {
         local_path: entry.path_to_string(),
         remote_path: "".to_string(),
         priority,
+        link: is_recorded_link(context, &entry.path),
     })
 }
 
-fn get_file_priority_by_file_priority(entry: &DirEntry) -> Option<usize> {
+fn get_file_priority_by_file_priority(context: &Context, entry: &DirEntry) -> Option<usize> {
     let mut min_list = vec![];
 
     if let Some(priorities) = entry.entry_file_priority.as_ref() {
         for priority in priorities {
-            if priority.content == "" {
+            if compare_file_by_rule(context, &entry.path, None, &priority.predicates, &priority.content).is_ok() {
                 min_list.push(priority.priority);
-                continue;
-            }
-
-            match compare_file_content_by_regex(&entry.path, &priority.content) {
-                true => min_list.push(priority.priority),
-                _ => {}
             }
         }
     }
 
-    return match min_list.is_empty() {
+    match min_list.is_empty() {
         true => None,
-        false => min_list.iter().min().map(|&value| value),
-    };
+        false => min_list.iter().min().copied(),
+    }
 }
 
-fn delete_not_exist_entries(entries: &mut Vec<DirEntry>) {
-    entries.retain(|entry| entry.path.exists());
+fn delete_not_exist_entries(context: &Context, entries: &mut Vec<DirEntry>) {
+    entries.retain(|entry| context.fs.exists(&entry.path));
 }
 
 fn delete_entries_with_only_filter(entries: &mut Vec<DirEntry>) {
     entries.retain(|entry| {
-        !(entry.entry_file_filter != None
-            && entry.entry_dir_priority == None
-            && entry.entry_file_priority == None
-            && entry.entry_dir_file_priority == None
+        !((entry.entry_file_filter.is_some() || entry.entry_exclude.is_some())
+            && entry.entry_dir_priority.is_none()
+            && entry.entry_file_priority.is_none()
+            && entry.entry_dir_file_priority.is_none()
             && !entry.selected)
     });
 }
 
-fn add_file_filter(entries: &mut Vec<DirEntry>) {
+fn add_file_filter(context: &Context, entries: &mut Vec<DirEntry>) {
     for entry in entries.clone().iter() {
         if let Some(filters) = entry.entry_file_filter.as_ref() {
             for sub_entry in entries.iter_mut() {
                 if sub_entry.path == entry.path
                     || !sub_entry.path_to_string().contains(&entry.path_to_string())
-                    || sub_entry.is_file()
+                    || context.fs.is_file(&sub_entry.path)
                 {
                     continue;
                 }
@@ -320,7 +581,7 @@ fn add_file_filter(entries: &mut Vec<DirEntry>) {
                     let entry_depth = entry.path_depth();
                     let sub_entry_depth = sub_entry.path_depth();
 
-                    if let None = sub_entry.entry_file_filter {
+                    if sub_entry.entry_file_filter.is_none() {
                         sub_entry.entry_file_filter = Some(vec![]);
                     }
 
@@ -333,7 +594,9 @@ fn add_file_filter(entries: &mut Vec<DirEntry>) {
                                 EntryFileFilter {
                                     regex: filter.regex.clone(),
                                     content: filter.content.clone(),
+                                    predicates: filter.predicates.clone(),
                                     deep: Some((entry_depth + deep - sub_entry_depth) as i8),
+                                    root: filter.root.clone(),
                                 }
                             } else {
                                 continue;
@@ -349,45 +612,310 @@ fn add_file_filter(entries: &mut Vec<DirEntry>) {
             }
 
             entries.retain(|sub_entry| {
-                !(sub_entry.is_file() && !compare_file_by_filters(sub_entry, filters))
+                !context.fs.is_file(&sub_entry.path) || compare_file_by_filters(context, sub_entry, filters)
             });
         }
     }
     delete_entries_with_only_filter(entries);
 }
 
-fn compare_file_by_filters(entry: &DirEntry, filters: &Vec<EntryFileFilter>) -> bool {
-    for filter in filters.iter() {
-        if !compare_entry_name_by_regex(&entry.short_name(), &filter.regex) {
+/// Drops the entries below a directory that one of its excludes matches, on
+/// the entry itself or on any directory between them, and hands the excludes
+/// still in reach down to the remaining subdirectories.
+fn add_excludes(context: &Context, entries: &mut Vec<DirEntry>) {
+    for entry in entries.clone().iter() {
+        let Some(excludes) = entry.entry_exclude.as_ref() else {
             continue;
+        };
+
+        let entry_depth = entry.path_depth();
+
+        entries.retain(|sub_entry| {
+            if sub_entry.path == entry.path || !sub_entry.path.starts_with(&entry.path) {
+                return true;
+            }
+
+            !sub_entry.path.ancestors().take(sub_entry.path_depth() - entry_depth).any(|path| {
+                let level = path.components().count() - entry_depth;
+
+                excludes.iter().any(|exclude| reaches(exclude.deep, level) && compare_entry_by_pattern(path, &exclude.root, &exclude.regex))
+            })
+        });
+
+        for sub_entry in entries.iter_mut() {
+            if sub_entry.path == entry.path || !sub_entry.path.starts_with(&entry.path) || context.fs.is_file(&sub_entry.path) {
+                continue;
+            }
+
+            let sub_entry_depth = sub_entry.path_depth();
+
+            for exclude in excludes.iter() {
+                let new_exclude = match exclude.deep {
+                    Some(deep) if deep as usize + entry_depth >= sub_entry_depth => EntryExclude {
+                        regex: exclude.regex.clone(),
+                        deep: Some((entry_depth + deep as usize - sub_entry_depth) as i8),
+                        root: exclude.root.clone(),
+                    },
+                    Some(_) => continue,
+                    None => exclude.clone(),
+                };
+
+                let sub_entry_excludes = sub_entry.entry_exclude.get_or_insert_with(Vec::new);
+
+                if !sub_entry_excludes.contains(&new_exclude) {
+                    sub_entry_excludes.push(new_exclude);
+                }
+            }
         }
+    }
+}
+
+/// Whether a rule with depth `deep` applies `level` levels below its entry.
+fn reaches(deep: Option<i8>, level: usize) -> bool {
+    deep.map(|deep| level <= deep as usize + 1).unwrap_or(true)
+}
 
-        if filter.content == "" {
-            return true;
+/// The excludes that still apply one level further down.
+fn next_level_excludes(excludes: &[EntryExclude]) -> Vec<EntryExclude> {
+    excludes
+        .iter()
+        .filter(|exclude| exclude.deep != Some(-1))
+        .map(|exclude| EntryExclude {
+            regex: exclude.regex.clone(),
+            deep: exclude.deep.map(|deep| deep - 1),
+            root: exclude.root.clone(),
+        })
+        .collect()
+}
+
+fn is_excluded(path: &Path, excludes: Option<&[EntryExclude]>) -> bool {
+    excludes
+        .unwrap_or_default()
+        .iter()
+        .any(|exclude| compare_entry_by_pattern(path, &exclude.root, &exclude.regex))
+}
+
+fn compare_file_by_filters(context: &Context, entry: &DirEntry, filters: &[EntryFileFilter]) -> bool {
+    filters
+        .iter()
+        .any(|filter| compare_file_by_rule(context, &entry.path, Some((&filter.root, &filter.regex)), &filter.predicates, &filter.content).is_ok())
+}
+
+/// Checks a rule against the file at `path` in the planner's order: the
+/// name against `name` (a root and a pattern), the predicates, then the
+/// content. `Err` tells which of them failed.
+fn compare_file_by_rule(
+    context: &Context,
+    path: &Path,
+    name: Option<(&str, &Pattern)>,
+    predicates: &[FilePredicate],
+    content: &Pattern,
+) -> Result<(), &'static str> {
+    if let Some((root, pattern)) = name {
+        if !compare_entry_by_pattern(path, root, pattern) {
+            return Err("name did not match");
         }
+    }
+
+    if !compare_file_by_predicates(context, path, predicates) {
+        return Err("predicates did not match");
+    }
+
+    if !content.is_empty() && !compare_file_content_by_regex(context, path, content) {
+        return Err("content did not match");
+    }
+
+    Ok(())
+}
+
+fn compare_entry_by_pattern(path: &Path, root: &str, pattern: &Pattern) -> bool {
+    pattern.matches_path(Path::new(root), path)
+}
 
-        match compare_file_content_by_regex(&entry.path, &filter.content) {
-            true => return true,
+fn compare_file_by_predicates(context: &Context, path: &Path, predicates: &[FilePredicate]) -> bool {
+    if predicates.is_empty() {
+        return true;
+    }
+
+    let Ok(metadata) = context.fs.metadata(path) else {
+        return false;
+    };
+
+    let now = SystemTime::now();
+
+    predicates.iter().all(|predicate| predicate.is_match(&metadata, now))
+}
+
+fn compare_file_content_by_regex(context: &Context, path: &Path, regex: &Pattern) -> bool {
+    content_service::is_match(context.fs, path, regex, context.content)
+}
+
+/// The steps of one template entry at or above `path`: the rules it
+/// carries, whether the walk below it can reach `path` and how each of its
+/// rules matches `path` there.
+fn explain_entry(context: &Context, entry: &DirEntry, path: &Path, steps: &mut Vec<ExplainStep>) {
+    let mut step = |at: &Path, message: String| steps.push(ExplainStep { path: at.to_path_buf(), message });
+
+    describe_rules(entry).into_iter().for_each(|rule| step(&entry.path, format!("carries {}", rule)));
+
+    let level = path.components().count() - entry.path.components().count();
+    let mut walked = path.ancestors().take(level).collect::<Vec<&Path>>();
+    walked.reverse();
+
+    for (index, at) in walked.into_iter().enumerate() {
+        let excluded = entry
+            .entry_exclude
+            .iter()
+            .flatten()
+            .find(|exclude| reaches(exclude.deep, index + 1) && compare_entry_by_pattern(at, &exclude.root, &exclude.regex));
+
+        if let Some(exclude) = excluded {
+            step(at, format!("left out by exclude `{}`", exclude.regex));
+            return;
+        }
+
+        match walk_kind(context, at) {
+            None => {
+                step(at, format!("left out, {}", skip_reason(context, at)));
+                return;
+            }
+            Some(FileKind::Dir) if !enters(context, at) => {
+                step(at, "not entered, the link leads back to one of its ancestors".to_string());
+                return;
+            }
             _ => {}
         }
     }
-    false
+
+    if walk_kind(context, path) != Some(FileKind::File) {
+        step(path, "a directory, explain a file below it to see how the rules apply".to_string());
+        return;
+    }
+
+    if level == 0 {
+        if entry.selected {
+            step(path, "selected".to_string());
+        }
+
+        for priority in entry.entry_file_priority.iter().flatten() {
+            let outcome = compare_file_by_rule(context, path, None, &priority.predicates, &priority.content);
+            step(path, format!("{}: {}", describe_file_priority(priority), outcome_text(outcome)));
+        }
+
+        return;
+    }
+
+    if entry.is_just_selected() {
+        step(path, match level {
+            1 => "taken, its directory is selected as a whole".to_string(),
+            _ => "out of reach, a selected directory only takes the files directly inside it".to_string(),
+        });
+    } else if entry.selected {
+        step(&entry.path, "selected but carrying rules, only the files a priority rule reaches are taken".to_string());
+    }
+
+    for filter in entry.entry_file_filter.iter().flatten() {
+        let outcome = match reaches(filter.deep, level) {
+            true => compare_file_by_rule(context, path, Some((&filter.root, &filter.regex)), &filter.predicates, &filter.content),
+            false => Err("out of reach"),
+        };
+
+        step(path, format!("{}: {}", describe_filter(filter), outcome_text(outcome)));
+    }
+
+    if let Some(parent) = path.parent().filter(|_| level > 1) {
+        for priority in entry.entry_dir_priority.iter().flatten() {
+            let outcome = match reaches(priority.deep, level - 1) {
+                true => compare_file_by_rule(context, parent, Some((&priority.root, &priority.regex)), &[], &Pattern::default()),
+                false => Err("out of reach"),
+            };
+
+            step(parent, format!("{}: {}", describe_dir_priority(priority), outcome_text(outcome)));
+        }
+    }
+
+    for priority in entry.entry_dir_file_priority.iter().flatten() {
+        let outcome = match reaches(priority.deep, level) {
+            true => compare_file_by_rule(context, path, Some((&priority.root, &priority.regex)), &priority.predicates, &priority.content),
+            false => Err("out of reach"),
+        };
+
+        step(path, format!("{}: {}", describe_dir_file_priority(priority), outcome_text(outcome)));
+    }
 }
 
-fn compare_entry_name_by_regex(name: &str, regex: &str) -> bool {
-    let regex = Regex::new(regex).unwrap();
+/// Rules of `entry`, naming the parent entry of those it inherited.
+fn describe_rules(entry: &DirEntry) -> Vec<String> {
+    let inherited = |root: &str| match Path::new(root) == entry.path {
+        true => String::new(),
+        false => format!(", from {}", root),
+    };
+    let mut rules = vec![];
+
+    rules.extend(entry.entry_file_filter.iter().flatten().map(|filter| describe_filter(filter) + &inherited(&filter.root)));
+    rules.extend(entry.entry_dir_priority.iter().flatten().map(|priority| describe_dir_priority(priority) + &inherited(&priority.root)));
+    rules.extend(entry.entry_dir_file_priority.iter().flatten().map(|priority| describe_dir_file_priority(priority) + &inherited(&priority.root)));
+    rules.extend(entry.entry_exclude.iter().flatten().map(|exclude| format!("exclude `{}`, {}{}", exclude.regex, reach(exclude.deep), inherited(&exclude.root))));
 
-    return regex.is_match(name);
+    rules
 }
 
-fn compare_file_content_by_regex(path: &Path, regex: &str) -> bool {
-    if let Ok(content) = file_service::read_file(path) {
-        let regex = Regex::new(regex).unwrap();
+fn describe_filter(filter: &EntryFileFilter) -> String {
+    format!("filter `{}`{}, {}", filter.regex, conditions(&filter.content, &filter.predicates), reach(filter.deep))
+}
+
+fn describe_dir_priority(priority: &EntryDirPriority) -> String {
+    format!("dir-priority `{}` priority {}, {}", priority.regex, priority.priority, reach(priority.deep))
+}
+
+fn describe_dir_file_priority(priority: &EntryDirFilePriority) -> String {
+    format!(
+        "dir-file-priority `{}` priority {}{}, {}",
+        priority.regex,
+        priority.priority,
+        conditions(&priority.content, &priority.predicates),
+        reach(priority.deep)
+    )
+}
+
+fn describe_file_priority(priority: &EntryFilePriority) -> String {
+    format!("file priority {}{}", priority.priority, conditions(&priority.content, &priority.predicates))
+}
+
+fn conditions(content: &Pattern, predicates: &[FilePredicate]) -> String {
+    let mut text = String::new();
+
+    if !content.is_empty() {
+        text.push_str(&format!(" with content `{}`", content));
+    }
 
-        return regex.is_match(&content);
+    if !predicates.is_empty() {
+        text.push_str(&format!(" when {}", predicates.iter().map(|predicate| predicate.to_string()).collect::<Vec<String>>().join(" and ")));
     }
 
-    false
+    text
+}
+
+/// How many levels below the current directory a rule with `deep` left
+/// still applies to.
+fn reach(deep: Option<i8>) -> String {
+    match deep {
+        Some(deep) => format!("{} level(s) left", deep as i16 + 1),
+        None => "every level".to_string(),
+    }
+}
+
+fn skip_reason(context: &Context, path: &Path) -> &'static str {
+    match context.fs.kind(path) {
+        FileKind::Special => "it is a special file",
+        FileKind::Missing => "it does not exist",
+        FileKind::Symlink if context.links == LinkPolicy::Skip => "it is a symlink and links are skipped",
+        _ => "it is a broken symlink",
+    }
+}
+
+fn outcome_text(outcome: Result<(), &'static str>) -> &'static str {
+    outcome.err().unwrap_or("matched")
 }
 
 fn insert_command(set: &mut HashSet<Command>, item: Command) {
@@ -397,15 +925,416 @@ fn insert_command(set: &mut HashSet<Command>, item: Command) {
                 set.remove(&item);
                 set.insert(item);
             }
-            (Some(old_priority), Some(new_priority)) => {
-                if new_priority < &old_priority {
-                    set.remove(&item);
-                    set.insert(item);
-                }
+            (Some(old_priority), Some(new_priority)) if new_priority < &old_priority => {
+                set.remove(&item);
+                set.insert(item);
             }
             _ => {}
         }
     } else {
         set.insert(item);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::{self, Read};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::path::{Path, PathBuf};
+
+    use crate::filesystems::file_system::FileSystem;
+    use crate::filesystems::memory_file_system::{MemoryFile, MemoryFileSystem};
+    use crate::mappers::{dir_entry_to_commands, template_to_dir_entry};
+    use crate::models::command::Command;
+    use crate::models::content_policy::ContentPolicy;
+    use crate::models::file_id::FileId;
+    use crate::models::file_kind::FileKind;
+    use crate::models::file_metadata::FileMetadata;
+    use crate::models::link_policy::LinkPolicy;
+
+    /// `tree()`, except that listing `/data/src` panics.
+    struct Broken(MemoryFileSystem);
+
+    impl FileSystem for Broken {
+        fn exists(&self, path: &Path) -> bool {
+            self.0.exists(path)
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            self.0.is_dir(path)
+        }
+
+        fn is_file(&self, path: &Path) -> bool {
+            self.0.is_file(path)
+        }
+
+        fn kind(&self, path: &Path) -> FileKind {
+            self.0.kind(path)
+        }
+
+        fn id(&self, path: &Path) -> Option<FileId> {
+            self.0.id(path)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            assert_ne!(path, Path::new("/data/src"), "listing failed");
+            self.0.read_dir(path)
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+            self.0.open(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+            self.0.metadata(path)
+        }
+    }
+
+    fn tree() -> MemoryFileSystem {
+        MemoryFileSystem::with_files(&[
+            ("/data/notes.txt", "todo"),
+            ("/data/photo.jpg", ""),
+            ("/data/src/main.rs", "fn main() {}"),
+            ("/data/src/lib.rs", "pub mod deep;"),
+            ("/data/src/deep/mod.rs", "fn main() {}"),
+            ("/data/invoices/a.pdf", ""),
+            ("/data/invoices/old/b.pdf", ""),
+            ("/data/invoices/old/c.txt", "paid"),
+        ])
+    }
+
+    /// Commands planned for `template` against `tree()` on `threads` threads.
+    fn plan(template: &str, threads: usize) -> HashSet<Command> {
+        let entries = template_to_dir_entry::map(template.to_string(), None).unwrap();
+
+        dir_entry_to_commands::map(entries, &tree(), &ContentPolicy::default(), LinkPolicy::Follow, threads, &|_, _| {})
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    /// `tree()` with a link back to `/data`, links to a file, to a sibling
+    /// directory and to nothing, and a special file.
+    fn linked_tree() -> MemoryFileSystem {
+        let mut fs = tree();
+
+        fs.add_link("/data/loop", "/data");
+        fs.add_link("/data/latest", "/data/notes.txt");
+        fs.add_link("/data/bills", "/data/invoices/old");
+        fs.add_link("/data/dangling", "/nowhere");
+        fs.add_special("/data/fifo");
+
+        fs
+    }
+
+    fn plan_links(template: &str, links: LinkPolicy) -> HashSet<Command> {
+        let entries = template_to_dir_entry::map(template.to_string(), None).unwrap();
+
+        dir_entry_to_commands::map(entries, &linked_tree(), &ContentPolicy::default(), links, 1, &|_, _| {})
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    /// Local paths and priorities, sorted, since commands compare by path only.
+    fn golden(commands: &HashSet<Command>) -> Vec<(String, Option<usize>)> {
+        let mut golden = commands.iter().map(|command| (command.local_path.clone(), command.priority)).collect::<Vec<_>>();
+        golden.sort();
+        golden
+    }
+
+    fn command(local_path: &str, priority: Option<usize>) -> Command {
+        Command {
+            local_path: local_path.to_string(),
+            remote_path: String::new(),
+            priority,
+            link: false,
+        }
+    }
+
+    fn expected(commands: &[(&str, Option<usize>)]) -> Vec<(String, Option<usize>)> {
+        commands.iter().map(|(path, priority)| (path.to_string(), *priority)).collect()
+    }
+
+    #[test]
+    fn selected_directory_takes_the_files_directly_inside() {
+        assert_eq!(golden(&plan("/data>s", 1)), expected(&[("/data/notes.txt", None), ("/data/photo.jpg", None)]));
+    }
+
+    #[test]
+    fn filters_narrow_the_dir_file_priorities() {
+        assert_eq!(
+            golden(&plan(r"/data>1[{\.txt$,}]2[{.*,,4}]", 1)),
+            expected(&[("/data/invoices/old/c.txt", Some(4)), ("/data/notes.txt", Some(4))])
+        );
+        assert_eq!(
+            golden(&plan(r"/data>1[{\.rs$,1}]2[{.*,,4}]", 1)),
+            expected(&[("/data/src/lib.rs", Some(4)), ("/data/src/main.rs", Some(4))])
+        );
+        assert_eq!(
+            golden(&plan(r"/data>1[{\.rs$,,main}]2[{.*,,4}]", 1)),
+            expected(&[("/data/src/deep/mod.rs", Some(4)), ("/data/src/main.rs", Some(4))])
+        );
+    }
+
+    #[test]
+    fn dir_priorities_take_the_files_of_matching_directories() {
+        assert_eq!(golden(&plan("/data>3[{^invoices$,,2}]", 1)), expected(&[("/data/invoices/a.pdf", Some(2))]));
+        assert_eq!(golden(&plan("/data>3[{^old$,0,2}]", 1)), expected(&[]));
+        assert_eq!(
+            golden(&plan("/data>3[{^old$,1,2}]", 1)),
+            expected(&[("/data/invoices/old/b.pdf", Some(2)), ("/data/invoices/old/c.txt", Some(2))])
+        );
+    }
+
+    #[test]
+    fn dir_file_priorities_match_names_then_content() {
+        assert_eq!(golden(&plan(r"/data>2[{\.rs$,0,3}]", 1)), expected(&[]));
+        assert_eq!(
+            golden(&plan(r"/data>2[{\.rs$,1,3}]", 1)),
+            expected(&[("/data/src/lib.rs", Some(3)), ("/data/src/main.rs", Some(3))])
+        );
+        assert_eq!(
+            golden(&plan(r"/data>2[{\.rs$,,3,main}]", 1)),
+            expected(&[("/data/src/deep/mod.rs", Some(3)), ("/data/src/lib.rs", None), ("/data/src/main.rs", Some(3))])
+        );
+    }
+
+    #[test]
+    fn excludes_leave_out_whole_directories() {
+        assert_eq!(
+            golden(&plan(r"/data>2[{.*,,5}]5[{^(old|src)$,}]", 1)),
+            expected(&[("/data/invoices/a.pdf", Some(5)), ("/data/notes.txt", Some(5)), ("/data/photo.jpg", Some(5))])
+        );
+    }
+
+    #[test]
+    fn overlapping_entries_keep_the_lowest_priority() {
+        assert_eq!(
+            golden(&plan("/data>2[{\\.rs$,,5}]3[{^src$,,2}]\n/data/src/main.rs>4[{1}]\n/data/notes.txt>s", 1)),
+            expected(&[
+                ("/data/notes.txt", None),
+                ("/data/src/deep/mod.rs", Some(5)),
+                ("/data/src/lib.rs", Some(2)),
+                ("/data/src/main.rs", Some(1)),
+            ])
+        );
+        assert_eq!(golden(&plan("/data>2[{notes,0,5}]\n/data/notes.txt>s", 1)), expected(&[("/data/notes.txt", Some(5))]));
+    }
+
+    #[test]
+    fn predicates_change_the_priority_of_a_file() {
+        let mut fs = MemoryFileSystem::default();
+
+        fs.add_file("/media/notes.txt", MemoryFile { modified: Some(1_700_000_000), ..Default::default() });
+        fs.add_file("/media/movie.mkv", MemoryFile { size: Some(3 << 30), modified: Some(1_700_000_000), ..Default::default() });
+        fs.add_file("/media/scan.pdf", MemoryFile { modified: Some(1_000_000_000), ..Default::default() });
+        fs.add_file("/media/unknown.bin", MemoryFile::default());
+
+        let entries = template_to_dir_entry::map("/media>2[{.*,,5}{.*,,1}(size>=1G){.*,,3}(modified<2010-01-01)]".to_string(), None).unwrap();
+        let commands = dir_entry_to_commands::map(entries, &fs, &ContentPolicy::default(), LinkPolicy::Follow, 1, &|_, _| {})
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<Command>>();
+
+        assert_eq!(
+            golden(&commands),
+            expected(&[("/media/movie.mkv", Some(1)), ("/media/notes.txt", Some(5)), ("/media/scan.pdf", Some(3)), ("/media/unknown.bin", Some(5))])
+        );
+    }
+
+    #[test]
+    fn links_are_skipped_followed_or_recorded() {
+        let data = [
+            ("/data/invoices/a.pdf", Some(1)),
+            ("/data/invoices/old/b.pdf", Some(1)),
+            ("/data/invoices/old/c.txt", Some(1)),
+            ("/data/notes.txt", Some(1)),
+            ("/data/photo.jpg", Some(1)),
+            ("/data/src/deep/mod.rs", Some(1)),
+            ("/data/src/lib.rs", Some(1)),
+            ("/data/src/main.rs", Some(1)),
+        ];
+
+        assert_eq!(golden(&plan_links("/data>2[{.*,,1}]", LinkPolicy::Skip)), expected(&data));
+        assert_eq!(
+            golden(&plan_links("/data>2[{.*,,1}]", LinkPolicy::Follow)),
+            expected(&[&[("/data/bills/b.pdf", Some(1)), ("/data/bills/c.txt", Some(1))], &data[..3], &[("/data/latest", Some(1))], &data[3..]].concat())
+        );
+        assert_eq!(
+            golden(&plan_links("/data>2[{.*,,1}]", LinkPolicy::Record)),
+            expected(
+                &[
+                    &[("/data/bills", Some(1)), ("/data/dangling", Some(1))],
+                    &data[..3],
+                    &[("/data/latest", Some(1)), ("/data/loop", Some(1))],
+                    &data[3..],
+                ]
+                .concat()
+            )
+        );
+    }
+
+    #[test]
+    fn template_roots_are_followed_whatever_the_policy() {
+        for links in [LinkPolicy::Skip, LinkPolicy::Follow, LinkPolicy::Record] {
+            assert_eq!(
+                golden(&plan_links("/data/bills>s\n/data/latest>s\n/data/dangling>s\n/data/fifo>s", links)),
+                expected(&[("/data/bills/b.pdf", None), ("/data/bills/c.txt", None), ("/data/latest", None)]),
+                "{:?}",
+                links
+            );
+        }
+        let recorded = |template: &str| {
+            let mut links = plan_links(template, LinkPolicy::Record)
+                .into_iter()
+                .filter(|command| command.link)
+                .map(|command| command.local_path)
+                .collect::<Vec<String>>();
+            links.sort();
+            links
+        };
+
+        assert_eq!(recorded("/data>s"), ["/data/bills", "/data/dangling", "/data/latest", "/data/loop"]);
+        assert!(recorded("/data/latest>s\n/data/bills>s").is_empty());
+        assert_eq!(
+            golden(&plan_links("/data/loop>3[{^src$,,2}]", LinkPolicy::Follow)),
+            expected(&[("/data/loop/src/lib.rs", Some(2)), ("/data/loop/src/main.rs", Some(2))])
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn local_path_reverses_remote_path() {
+        for local in ["/data/notes.txt", "/data/src/deep/mod.rs", "/a.txt"] {
+            let remote = dir_entry_to_commands::remote_path(local);
+            let file_name = Path::new(local).file_name().unwrap().to_string_lossy();
+
+            assert_eq!(dir_entry_to_commands::local_path(&format!("{}/{}", remote, file_name)), PathBuf::from(local), "{}", remote);
+        }
+        assert_eq!(dir_entry_to_commands::remote_path("/data/notes.txt"), "/data");
+        assert_eq!(dir_entry_to_commands::local_path("data//src/main.rs"), PathBuf::from("/data/src/main.rs"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn local_path_reverses_remote_path() {
+        for local in [r"C:\Users\me\notes.txt", r"D:\a.txt"] {
+            let remote = dir_entry_to_commands::remote_path(local);
+            let file_name = Path::new(local).file_name().unwrap().to_string_lossy();
+
+            assert_eq!(dir_entry_to_commands::local_path(&format!("{}/{}", remote, file_name)), PathBuf::from(local), "{}", remote);
+        }
+        assert_eq!(dir_entry_to_commands::remote_path(r"C:\Users\me\notes.txt"), r"C\Users\me");
+    }
+
+    #[test]
+    fn insert_command_prefers_a_priority_then_the_lowest() {
+        for (first, second, kept) in [
+            (None, None, None),
+            (None, Some(3), Some(3)),
+            (Some(3), None, Some(3)),
+            (Some(3), Some(1), Some(1)),
+            (Some(1), Some(3), Some(1)),
+        ] {
+            let mut set = HashSet::new();
+
+            dir_entry_to_commands::insert_command(&mut set, command("/data/notes.txt", first));
+            dir_entry_to_commands::insert_command(&mut set, command("/data/notes.txt", second));
+
+            assert_eq!(golden(&set), expected(&[("/data/notes.txt", kept)]), "{:?} then {:?}", first, second);
+        }
+    }
+
+    #[test]
+    fn threads_do_not_change_the_plan() {
+        for template in [
+            "/data>s",
+            r"/data>1[{\.txt$,}]2[{.*,,4}]",
+            "/data>3[{^old$,1,2}]5[{^src$,}]",
+            "/data>2[{\\.rs$,,5}]3[{^src$,,2}]\n/data/src/main.rs>4[{1}]\n/data/notes.txt>s",
+        ] {
+            let single = golden(&plan(template, 1));
+
+            for threads in [2, 4, 16] {
+                assert_eq!(golden(&plan(template, threads)), single, "{} on {} threads", template, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn a_panic_while_visiting_reaches_the_caller() {
+        let entries = template_to_dir_entry::map("/data>2[{.*,,1}]".to_string(), None).unwrap();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            dir_entry_to_commands::map(entries, &Broken(tree()), &ContentPolicy::default(), LinkPolicy::Follow, 4, &|_, _| {})
+        }));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn explain_evaluates_the_rules_of_one_path() {
+        let entries = template_to_dir_entry::map(r"/data>1[{\.rs$,,main}]2[{.*,1,4}]3[{^old$,,2}]5[{^deep$,}]".to_string(), None).unwrap();
+        let explain = |path: &str| {
+            dir_entry_to_commands::explain(entries.clone(), &tree(), &ContentPolicy::default(), LinkPolicy::Follow, 4, Path::new(path))
+                .into_iter()
+                .map(|step| step.to_string())
+                .collect::<Vec<String>>()
+        };
+        let carries = [
+            r"/data: carries filter `\.rs$` with content `main`, every level",
+            "/data: carries dir-priority `^old$` priority 2, every level",
+            "/data: carries dir-file-priority `.*` priority 4, 2 level(s) left",
+            "/data: carries exclude `^deep$`, every level",
+        ];
+
+        assert_eq!(
+            explain("/data/src/main.rs"),
+            [
+                &carries[..],
+                &[
+                    r"/data/src/main.rs: filter `\.rs$` with content `main`, every level: matched",
+                    "/data/src: dir-priority `^old$` priority 2, every level: name did not match",
+                    "/data/src/main.rs: dir-file-priority `.*` priority 4, 2 level(s) left: matched",
+                    "/data/src/main.rs: planned with priority 4",
+                ],
+            ]
+            .concat()
+        );
+        assert_eq!(
+            explain("/data/src/lib.rs"),
+            [
+                &carries[..],
+                &[
+                    r"/data/src/lib.rs: filter `\.rs$` with content `main`, every level: content did not match",
+                    "/data/src: dir-priority `^old$` priority 2, every level: name did not match",
+                    "/data/src/lib.rs: dir-file-priority `.*` priority 4, 2 level(s) left: matched",
+                    "/data/src/lib.rs: not planned",
+                ],
+            ]
+            .concat()
+        );
+        assert_eq!(
+            explain("/data/src/deep/mod.rs"),
+            [&carries[..], &["/data/src/deep: left out by exclude `^deep$`", "/data/src/deep/mod.rs: not planned"]].concat()
+        );
+        assert_eq!(explain("/elsewhere/a.txt"), ["/elsewhere/a.txt: not planned"]);
+        let entries = template_to_dir_entry::map("/data>1[{\\.txt$,}]\n/data/src>s".to_string(), None).unwrap();
+        let steps = dir_entry_to_commands::explain(entries, &tree(), &ContentPolicy::default(), LinkPolicy::Follow, 4, Path::new("/data/src/lib.rs"))
+            .into_iter()
+            .map(|step| step.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            steps,
+            [
+                "/data: template entry left out, it only has filters and excludes, they apply to the entries below it",
+                r"/data/src: carries filter `\.txt$`, every level, from /data",
+                "/data/src: selected but carrying rules, only the files a priority rule reaches are taken",
+                r"/data/src/lib.rs: filter `\.txt$`, every level: name did not match",
+                "/data/src/lib.rs: not planned",
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::dir_entry::DirEntry;
+use crate::models::file_predicate::FilePredicate;
+
+/// Writes entries back in the canonical line format,
+/// `path>1[{regex,deep,content}]2[{regex,deep,priority,content}]3[{regex,deep,priority}]4[{priority,content}]5[{regex,deep}]s`,
+/// with `(predicates)` after the rules of groups 1, 2 and 4 that have some.
+/// Paths and patterns holding a character the format would read as a
+/// separator are written in double quotes.
+pub fn map(entries: &[DirEntry]) -> String {
+    let mut template = String::new();
+
+    for entry in entries {
+        template.push_str(&line(entry));
+        template.push('\n');
+    }
+
+    template
+}
+
+fn line(entry: &DirEntry) -> String {
+    let path = literal(&entry.path_to_string());
+
+    let mut line = match path.contains('>') || path.starts_with(['"', '#']) || path.starts_with(char::is_whitespace) {
+        true => format!("{}>", quote(&path)),
+        false => format!("{}>", path),
+    };
+
+    if let Some(filters) = entry.entry_file_filter.as_ref() {
+        let rules = filters
+            .iter()
+            .map(|filter| format!("{{{},{}{}}}{}", regex(&filter.regex.to_string()), deep(filter.deep), content(&filter.content.to_string()), predicates(&filter.predicates)))
+            .collect::<Vec<String>>();
+
+        line.push_str(&format!("1[{}]", rules.join(",")));
+    }
+
+    if let Some(priorities) = entry.entry_dir_file_priority.as_ref() {
+        let rules = priorities
+            .iter()
+            .map(|priority| {
+                format!(
+                    "{{{},{},{}{}}}{}",
+                    regex(&priority.regex.to_string()),
+                    deep(priority.deep),
+                    priority.priority,
+                    content(&priority.content.to_string()),
+                    predicates(&priority.predicates)
+                )
+            })
+            .collect::<Vec<String>>();
+
+        line.push_str(&format!("2[{}]", rules.join(",")));
+    }
+
+    if let Some(priorities) = entry.entry_dir_priority.as_ref() {
+        let rules = priorities
+            .iter()
+            .map(|priority| format!("{{{},{},{}}}", regex(&priority.regex.to_string()), deep(priority.deep), priority.priority))
+            .collect::<Vec<String>>();
+
+        line.push_str(&format!("3[{}]", rules.join(",")));
+    }
+
+    if let Some(priorities) = entry.entry_file_priority.as_ref() {
+        let rules = priorities
+            .iter()
+            .map(|priority| format!("{{{}{}}}{}", priority.priority, content(&priority.content.to_string()), predicates(&priority.predicates)))
+            .collect::<Vec<String>>();
+
+        line.push_str(&format!("4[{}]", rules.join(",")));
+    }
+
+    if let Some(excludes) = entry.entry_exclude.as_ref() {
+        let rules = excludes
+            .iter()
+            .map(|exclude| format!("{{{},{}}}", regex(&exclude.regex.to_string()), deep(exclude.deep)))
+            .collect::<Vec<String>>();
+
+        line.push_str(&format!("5[{}]", rules.join(",")));
+    }
+
+    if entry.selected {
+        line.push('s');
+    }
+
+    line
+}
+
+fn deep(deep: Option<i8>) -> String {
+    deep.map(|deep| deep.to_string()).unwrap_or_default()
+}
+
+/// Name regexes end at the first `,` or `}`.
+fn regex(regex: &str) -> String {
+    let regex = literal(regex);
+
+    match regex.contains([',', '}', '"']) || regex.starts_with(char::is_whitespace) {
+        true => quote(&regex),
+        false => regex,
+    }
+}
+
+/// Content is the last field so it may hold commas, but not `}`, and the
+/// parser trims its leading whitespace.
+fn content(content: &str) -> String {
+    if content.is_empty() {
+        return String::new();
+    }
+
+    let content = literal(content);
+
+    match content.contains(['}', '"']) || content.starts_with(char::is_whitespace) {
+        true => format!(",{}", quote(&content)),
+        false => format!(",{}", content),
+    }
+}
+
+/// `(size<2GB,age<7d)` after the rule, nothing when it has no predicates.
+fn predicates(predicates: &[FilePredicate]) -> String {
+    match predicates.is_empty() {
+        true => String::new(),
+        false => format!("({})", predicates.iter().map(|predicate| predicate.to_string()).collect::<Vec<String>>().join(",")),
+    }
+}
+
+/// Keeps a `${` of the entries from being read back as a variable.
+fn literal(text: &str) -> String {
+    text.replace("${", "$${")
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
@@ -0,0 +1,62 @@
+use crate::models::{
+    dir_entry::DirEntry,
+    file_predicate::FilePredicate,
+    template_document::{
+        DirFilePriorityRule, DirPriorityRule, ExcludeRule, FilePriorityRule, FilterRule, TemplateDocument,
+        TemplateDocumentEntry,
+    },
+};
+
+pub fn map(entries: &[DirEntry]) -> TemplateDocument {
+    TemplateDocument {
+        entries: entries.iter().map(entry).collect(),
+    }
+}
+
+fn entry(entry: &DirEntry) -> TemplateDocumentEntry {
+    TemplateDocumentEntry {
+        path: entry.path_to_string(),
+        selected: entry.selected,
+        filters: entry.entry_file_filter.iter().flatten()
+            .map(|filter| FilterRule {
+                regex: filter.regex.to_string(),
+                deep: filter.deep,
+                content: filter.content.to_string(),
+                predicates: predicates(&filter.predicates),
+            })
+            .collect(),
+        dir_file_priorities: entry.entry_dir_file_priority.iter().flatten()
+            .map(|priority| DirFilePriorityRule {
+                regex: priority.regex.to_string(),
+                deep: priority.deep,
+                priority: priority.priority,
+                content: priority.content.to_string(),
+                predicates: predicates(&priority.predicates),
+            })
+            .collect(),
+        dir_priorities: entry.entry_dir_priority.iter().flatten()
+            .map(|priority| DirPriorityRule {
+                regex: priority.regex.to_string(),
+                deep: priority.deep,
+                priority: priority.priority,
+            })
+            .collect(),
+        file_priorities: entry.entry_file_priority.iter().flatten()
+            .map(|priority| FilePriorityRule {
+                priority: priority.priority,
+                content: priority.content.to_string(),
+                predicates: predicates(&priority.predicates),
+            })
+            .collect(),
+        excludes: entry.entry_exclude.iter().flatten()
+            .map(|exclude| ExcludeRule {
+                regex: exclude.regex.to_string(),
+                deep: exclude.deep,
+            })
+            .collect(),
+    }
+}
+
+fn predicates(predicates: &[FilePredicate]) -> Vec<String> {
+    predicates.iter().map(|predicate| predicate.to_string()).collect()
+}
@@ -1,2 +1,6 @@
 pub mod template_to_dir_entry;
-pub mod dir_entry_to_commands;
\ No newline at end of file
+pub mod template_document_to_dir_entry;
+pub mod dir_entry_to_template;
+pub mod dir_entry_to_template_document;
+pub mod dir_entry_to_commands;
+pub mod commands_to_plan;
\ No newline at end of file
@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+
+use crate::models::{
+    dir_entry::DirEntry, entry_dir_file_priority::EntryDirFilePriority,
+    entry_dir_priority::EntryDirPriority, entry_exclude::EntryExclude, entry_file_filter::EntryFileFilter, file_predicate::FilePredicate,
+    entry_file_priority::EntryFilePriority, pattern::Pattern, template_error::TemplateError,
+    template_document::{TemplateDocument, TemplateDocumentEntry},
+};
+
+pub fn map(document: TemplateDocument) -> Result<Vec<DirEntry>, TemplateError> {
+    let mut entries = Vec::new();
+
+    for (index, entry) in document.entries.iter().enumerate() {
+        entries.push(map_entry(index, entry)?);
+    }
+
+    if entries.is_empty() {
+        return Err(TemplateError::in_field("entries".to_string(), "", "at least one template entry"));
+    }
+
+    Ok(entries)
+}
+
+/// Name used to point at entry `index` in errors and reports.
+pub fn location(index: usize) -> String {
+    format!("entries[{}]", index)
+}
+
+pub fn map_entry(index: usize, document_entry: &TemplateDocumentEntry) -> Result<DirEntry, TemplateError> {
+    let field = |name: &str| format!("{}.{}", location(index), name);
+
+    if document_entry.path.is_empty() {
+        return Err(TemplateError::in_field(field("path"), "", "a path"));
+    }
+
+    let path = PathBuf::from(&document_entry.path);
+    let str_path = path.as_path().display().to_string();
+    let mut entry = DirEntry {
+        path,
+        selected: document_entry.selected,
+        ..Default::default()
+    };
+
+    let mut filters = vec![];
+
+    for (i, rule) in document_entry.filters.iter().enumerate() {
+        let field = |name: &str| field(&format!("filters[{}].{}", i, name));
+
+        filters.push(EntryFileFilter {
+            regex: regex(field("regex"), &rule.regex)?,
+            content: pattern(field("content"), &rule.content)?,
+            predicates: predicates(field("predicates"), &rule.predicates)?,
+            deep: deep(field("deep"), rule.deep)?,
+            root: str_path.clone(),
+        });
+    }
+
+    let mut dir_file_priorities = vec![];
+
+    for (i, rule) in document_entry.dir_file_priorities.iter().enumerate() {
+        let field = |name: &str| field(&format!("dir_file_priorities[{}].{}", i, name));
+
+        dir_file_priorities.push(EntryDirFilePriority {
+            regex: regex(field("regex"), &rule.regex)?,
+            content: pattern(field("content"), &rule.content)?,
+            predicates: predicates(field("predicates"), &rule.predicates)?,
+            priority: rule.priority,
+            deep: deep(field("deep"), rule.deep)?,
+            root: str_path.clone(),
+        });
+    }
+
+    let mut dir_priorities = vec![];
+
+    for (i, rule) in document_entry.dir_priorities.iter().enumerate() {
+        let field = |name: &str| field(&format!("dir_priorities[{}].{}", i, name));
+
+        dir_priorities.push(EntryDirPriority {
+            regex: regex(field("regex"), &rule.regex)?,
+            deep: deep(field("deep"), rule.deep)?,
+            priority: rule.priority,
+            root: str_path.clone(),
+        });
+    }
+
+    let mut file_priorities = vec![];
+
+    for (i, rule) in document_entry.file_priorities.iter().enumerate() {
+        let field = |name: &str| field(&format!("file_priorities[{}].{}", i, name));
+
+        file_priorities.push(EntryFilePriority {
+            content: pattern(field("content"), &rule.content)?,
+            predicates: predicates(field("predicates"), &rule.predicates)?,
+            priority: rule.priority,
+            root: str_path.clone(),
+        });
+    }
+
+    let mut excludes = vec![];
+
+    for (i, rule) in document_entry.excludes.iter().enumerate() {
+        let field = |name: &str| field(&format!("excludes[{}].{}", i, name));
+
+        excludes.push(EntryExclude {
+            regex: regex(field("regex"), &rule.regex)?,
+            deep: deep(field("deep"), rule.deep)?,
+            root: str_path.clone(),
+        });
+    }
+
+    entry.entry_file_filter = non_empty(filters);
+    entry.entry_dir_file_priority = non_empty(dir_file_priorities);
+    entry.entry_dir_priority = non_empty(dir_priorities);
+    entry.entry_file_priority = non_empty(file_priorities);
+    entry.entry_exclude = non_empty(excludes);
+
+    Ok(entry)
+}
+
+fn non_empty<T>(items: Vec<T>) -> Option<Vec<T>> {
+    match items.is_empty() {
+        true => None,
+        false => Some(items),
+    }
+}
+
+fn regex(field: String, source: &str) -> Result<Pattern, TemplateError> {
+    match source.is_empty() {
+        true => Err(TemplateError::in_field(field, source, "a name pattern")),
+        false => Pattern::rule(source).map_err(|error| TemplateError::in_field(field, source, &Pattern::expected(&error))),
+    }
+}
+
+fn pattern(field: String, source: &str) -> Result<Pattern, TemplateError> {
+    Pattern::content(source).map_err(|error| TemplateError::in_field(field, source, &Pattern::expected(&error)))
+}
+
+fn predicates(field: String, sources: &[String]) -> Result<Vec<FilePredicate>, TemplateError> {
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| FilePredicate::parse(source).ok_or_else(|| TemplateError::in_field(format!("{}[{}]", field, i), source, FilePredicate::EXPECTED)))
+        .collect()
+}
+
+fn deep(field: String, deep: Option<i8>) -> Result<Option<i8>, TemplateError> {
+    match deep {
+        Some(number) if number < 0 => Err(TemplateError::in_field(field, &number.to_string(), "a depth number or nothing")),
+        _ => Ok(deep),
+    }
+}
@@ -1,244 +1,919 @@
-use std::path::PathBuf;
-
-use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::models::{
     dir_entry::DirEntry, entry_dir_file_priority::EntryDirFilePriority,
-    entry_dir_priority::EntryDirPriority, entry_file_filter::EntryFileFilter,
-    entry_file_priority::EntryFilePriority,
+    entry_dir_priority::EntryDirPriority, entry_exclude::EntryExclude, entry_file_filter::EntryFileFilter,
+    entry_file_priority::EntryFilePriority, file_predicate::FilePredicate, pattern::Pattern,
+    template_error::TemplateError,
 };
 
-pub fn map(content: String) -> Result<Vec<DirEntry>, String> {
+/// `origin` is the template file, `include` paths are relative to its folder.
+pub fn map(content: String, origin: Option<&Path>) -> Result<Vec<DirEntry>, TemplateError> {
     let mut entries = Vec::new();
 
-    for line in content.lines() {
-        let mut entry = DirEntry::default();
+    for (_, entry) in parse_lines(&content, origin) {
+        entries.push(entry?);
+    }
 
-        entry.selected = is_selected(line);
+    if entries.is_empty() {
+        return Err(TemplateError::at(1, "", 0, "", "at least one template entry"));
+    }
 
-        let split = line.split(">").collect::<Vec<&str>>();
+    Ok(entries)
+}
 
-        if split.len() != 2 {
-            return Err("Error parsing".to_string());
-        }
+/// Parses every line independently, so callers can report all errors
+/// instead of stopping at the first one. Pairs each result with its line
+/// number, entries of included templates take the line of their `include`.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_lines(content: &str, origin: Option<&Path>) -> Vec<(usize, Result<DirEntry, TemplateError>)> {
+    let mut reader = TemplateReader {
+        variables: HashMap::new(),
+        including: origin.and_then(|origin| origin.canonicalize().ok()).into_iter().collect(),
+        results: vec![],
+    };
+
+    let dir = origin.and_then(Path::parent).unwrap_or(Path::new(""));
+
+    reader.read(content, dir, None, None);
+    reader.results
+}
 
-        let path = path(split[0]);
-        entry.path = path.clone();
+/// Whether `content` has `include` or `set` directives or `${NAME}`
+/// variables, which only the line format can express.
+pub fn has_directives(content: &str) -> bool {
+    content
+        .lines()
+        .filter(|line| !is_blank_or_comment(line))
+        .any(|line| directive(line, "include").is_some() || directive(line, "set").is_some() || line.replace("$${", "").contains("${"))
+}
 
-        let str_path = path.as_path().display().to_string();
+fn is_blank_or_comment(line: &str) -> bool {
+    let line = line.trim();
 
-        entry.entry_file_filter = file_filter(split[1])?;
-        entry.entry_dir_file_priority = dir_file_priority(split[1], str_path.clone())?;
-        entry.entry_dir_priority = dir_priority(split[1], str_path.clone())?;
-        entry.entry_file_priority = file_priority(split[1], str_path.clone())?;
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Offset of the argument of `keyword` when `line` is that directive,
+/// `include base.tpl` or `set NAME = value`.
+fn directive(line: &str, keyword: &str) -> Option<usize> {
+    let leading = line.len() - line.trim_start().len();
+    let rest = line[leading..].strip_prefix(keyword)?;
 
-        entries.push(entry);
+    match rest.starts_with(char::is_whitespace) {
+        true => Some(line.len() - rest.trim_start().len()),
+        false => None,
     }
+}
 
-    if entries.is_empty() {
-        return Err("Error parsing".to_string());
+/// Replaces every `${NAME}` of `text` by a `set` variable, an environment
+/// variable or, for `HOSTNAME`, the machine name. `$${` is a literal `${`.
+/// Fails with the `${NAME}` that could not be expanded.
+fn expand(text: &str, variables: &HashMap<String, String>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        expanded.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            return Err(rest[start..].to_string());
+        };
+
+        let name = &rest[start + 2..end];
+
+        let value = variables
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+            .or_else(|| (name == "HOSTNAME").then(|| gethostname::gethostname().to_string_lossy().to_string()));
+
+        match value {
+            Some(value) => expanded.push_str(&value),
+            None => return Err(rest[start..=end].to_string()),
+        }
+
+        rest = &rest[end + 1..];
     }
 
-    return Ok(entries);
-}
+    expanded.push_str(rest);
 
-fn is_selected(line: &str) -> bool {
-    line.chars().last().unwrap_or_default().eq(&'s')
+    Ok(expanded)
 }
 
-fn path(line: &str) -> PathBuf {
-    PathBuf::from(line)
-}
+const UNDEFINED_VARIABLE: &str = "a variable defined with `set NAME = value` or in the environment";
 
-fn file_filter(line: &str) -> Result<Option<Vec<EntryFileFilter>>, String> {
-    let regex = Regex::new(r"1\[([^]]*)\]").unwrap();
-    let mut filters = Vec::new();
+/// Reads a template line by line, inlining `include` directives and keeping
+/// the variables of `set` directives for the lines below them, including
+/// those of later included templates.
+struct TemplateReader {
+    variables: HashMap<String, String>,
+    /// Canonical paths of the templates being read, to detect include cycles.
+    including: Vec<PathBuf>,
+    results: Vec<(usize, Result<DirEntry, TemplateError>)>,
+}
 
-    if let Some(captures) = regex.captures(line) {
-        if let Some(group) = captures.get(1) {
-            let group = group.as_str();
-            let regex = Regex::new(r"\{(.+?),\s*(\d*|\s*),*\s*(.*?)\}").unwrap();
-
-            for cap in regex.captures_iter(group) {
-                let mut filter = EntryFileFilter::default();
-                if let Some(regex) = cap.get(1) {
-                    filter.regex = regex.as_str().to_string();
-                } else {
-                    return Err("Error parsing".to_string());
-                }
+impl TemplateReader {
+    /// `file` is the included template being read, `None` for the top one.
+    /// `include_line` is the top level `include` line that led to it.
+    fn read(&mut self, content: &str, dir: &Path, file: Option<&Path>, include_line: Option<usize>) {
+        for (index, line) in content.lines().enumerate() {
+            if is_blank_or_comment(line) {
+                continue;
+            }
 
-                if let Some(deep) = cap.get(2) {
-                    if let Ok(number) = deep.as_str().parse::<usize>() {
-                        filter.deep = Some(number as i8);
-                    } else {
-                        filter.deep = None
+            let number = index + 1;
+            let reported = include_line.unwrap_or(number);
+
+            let result = if let Some(offset) = directive(line, "include") {
+                self.include(number, line, offset, dir, reported).map(|_| None)
+            } else if let Some(offset) = directive(line, "set") {
+                self.set(number, line, offset).map(|_| None)
+            } else {
+                LineParser::new(number, line, &self.variables).parse().map(Some)
+            };
+
+            let result = match result {
+                Ok(None) => continue,
+                Ok(Some(entry)) => Ok(entry),
+                Err(mut error) => {
+                    if error.file.is_none() {
+                        error.file = file.map(|file| file.display().to_string());
                     }
-                } else {
-                    return Err("Error parsing".to_string());
+                    Err(error)
                 }
+            };
 
-                if let Some(content) = cap.get(3) {
-                    filter.content = content.as_str().to_string();
-                }
+            self.results.push((reported, result));
+        }
+    }
 
+    fn include(&mut self, number: usize, line: &str, offset: usize, dir: &Path, reported: usize) -> Result<(), TemplateError> {
+        let argument = line[offset..].trim_end();
+        let unquoted = match argument.len() > 1 && argument.starts_with('"') && argument.ends_with('"') {
+            true => argument[1..argument.len() - 1].replace("\"\"", "\""),
+            false => argument.to_string(),
+        };
 
-                filters.push(filter);
-            }
+        let expanded = expand(&unquoted, &self.variables)
+            .map_err(|variable| TemplateError::at(number, line, offset, &variable, UNDEFINED_VARIABLE))?;
+
+        let path = dir.join(expanded);
+
+        let canonical = path
+            .canonicalize()
+            .map_err(|error| TemplateError::at(number, line, offset, argument, &format!("a readable template ({})", error)))?;
+
+        if let Some(start) = self.including.iter().position(|including| *including == canonical) {
+            let cycle = self.including[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            return Err(TemplateError::at(number, line, offset, &cycle, "an include that does not lead back to a template being read"));
         }
+
+        let content = fs::read_to_string(&canonical)
+            .map_err(|error| TemplateError::at(number, line, offset, argument, &format!("a readable template ({})", error)))?;
+
+        self.including.push(canonical.clone());
+        self.read(&content, canonical.parent().unwrap_or(dir), Some(&path), Some(reported));
+        self.including.pop();
+
+        Ok(())
     }
 
-    if filters.is_empty() {
-        return Ok(None);
+    fn set(&mut self, number: usize, line: &str, offset: usize) -> Result<(), TemplateError> {
+        let argument = line[offset..].trim_end();
+
+        let Some((name, value)) = argument.split_once('=') else {
+            return Err(TemplateError::at(number, line, offset, argument, "`set NAME = value`"));
+        };
+
+        let name = name.trim();
+
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(TemplateError::at(number, line, offset, name, "a variable name made of letters, digits and `_`"));
+        }
+
+        let value_offset = offset + name.len() + argument[name.len()..].find('=').unwrap_or(0) + 1;
+
+        let value = expand(value.trim(), &self.variables).map_err(|variable| {
+            let offset = value_offset + value.find(&variable).unwrap_or(0);
+
+            TemplateError::at(number, line, offset, &variable, UNDEFINED_VARIABLE)
+        })?;
+
+        self.variables.insert(name.to_string(), value);
+
+        Ok(())
     }
+}
 
-    Ok(Some(filters))
+/// Parses one `path>1[{...}]2[{...}]3[{...}]4[{...}]5[{...}]s` line, keeping byte
+/// offsets into the line so errors can point at the exact column.
+///
+/// The path and any rule field may be wrapped in double quotes to hold
+/// characters that would otherwise end it (`>`, `,`, `}`). Inside quotes
+/// everything is literal and `""` stands for one `"`, backslashes keep
+/// their regex meaning. `${NAME}` is expanded in the path and patterns.
+struct LineParser<'a> {
+    number: usize,
+    line: &'a str,
+    position: usize,
+    variables: &'a HashMap<String, String>,
 }
 
-fn dir_file_priority(
-    line: &str,
-    path: String,
-) -> Result<Option<Vec<EntryDirFilePriority>>, String> {
-    let regex = Regex::new(r"2\[([^]]*)\]").unwrap();
-    let mut priorities = Vec::new();
+/// A field inside `{...}`, unquoted, with the byte offset it starts at.
+struct Field {
+    offset: usize,
+    text: String,
+    quoted: bool,
+}
 
-    if let Some(captures) = regex.captures(line) {
-        if let Some(group) = captures.get(1) {
-            let group = group.as_str();
-            let regex = Regex::new(r"\{(.+?),\s*(\d*|\s*),\s*(\d+),*\s*(.*?)\}").unwrap();
-
-            for cap in regex.captures_iter(group) {
-                let mut priority = EntryDirFilePriority::default();
-                if let Some(regex) = cap.get(1) {
-                    priority.regex = regex.as_str().to_string();
-                } else {
-                    return Err("Error parsing".to_string());
-                }
+/// The fields of one `{...}` rule, the offset of its closing `}` and the
+/// predicates of the `(...)` that may follow it.
+struct Rule {
+    fields: Vec<Field>,
+    close: usize,
+    predicates: Vec<Field>,
+}
 
-                if let Some(deep) = cap.get(2) {
-                    if let Ok(number) = deep.as_str().parse::<usize>() {
-                        priority.deep = Some(number as i8);
-                    } else {
-                        priority.deep = None
-                    }
-                } else {
-                    return Err("Error parsing".to_string());
+impl Rule {
+    fn field(&self, index: usize) -> Option<&Field> {
+        self.fields.get(index)
+    }
+}
+
+impl<'a> LineParser<'a> {
+    fn new(number: usize, line: &'a str, variables: &'a HashMap<String, String>) -> Self {
+        Self { number, line, position: 0, variables }
+    }
+
+    fn expand(&self, offset: usize, text: &str) -> Result<String, TemplateError> {
+        expand(text, self.variables).map_err(|variable| {
+            let offset = offset + text.find(&variable).unwrap_or(0);
+
+            self.error(offset, &variable, UNDEFINED_VARIABLE)
+        })
+    }
+
+    fn error(&self, offset: usize, fragment: &str, expected: &str) -> TemplateError {
+        TemplateError::at(self.number, self.line, offset, fragment, expected)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip(&mut self, separators: &[char]) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() && !separators.contains(&c) {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn parse(mut self) -> Result<DirEntry, TemplateError> {
+        let path = self.path()?;
+
+        if path.as_os_str().is_empty() {
+            return Err(self.error(0, "", "a path before `>`"));
+        }
+
+        let str_path = path.as_path().display().to_string();
+        let mut entry = DirEntry {
+            path,
+            ..Default::default()
+        };
+
+        loop {
+            self.skip(&[]);
+
+            let start = self.position;
+
+            let Some(c) = self.peek() else {
+                break;
+            };
+
+            if c == 's' && self.rest()[1..].trim().is_empty() {
+                entry.selected = true;
+                break;
+            }
+
+            if c == '>' {
+                return Err(self.error(start, ">", "a single `>` per line, quote paths that contain `>`"));
+            }
+
+            if !c.is_ascii_digit() {
+                return Err(self.error(start, self.rest(), "a rule group like `1[...]` or the `s` selection marker"));
+            }
+
+            self.position += 1;
+
+            if !('1'..='5').contains(&c) {
+                return Err(self.error(start, &self.line[start..self.position], "rule group 1, 2, 3, 4 or 5"));
+            }
+
+            if self.peek() != Some('[') {
+                return Err(self.error(start, &self.line[start..self.position], "`[` after the rule group number"));
+            }
+
+            self.position += 1;
+
+            match c {
+                '1' => {
+                    let rules = self.group_rules(start, c, 3)?;
+                    extend(&mut entry.entry_file_filter, file_filter(&self, &rules, &str_path)?)
+                }
+                '2' => {
+                    let rules = self.group_rules(start, c, 4)?;
+                    extend(&mut entry.entry_dir_file_priority, dir_file_priority(&self, &rules, &str_path)?)
                 }
+                '3' => {
+                    let rules = self.group_rules(start, c, 3)?;
+                    extend(&mut entry.entry_dir_priority, dir_priority(&self, &rules, &str_path)?)
+                }
+                '4' => {
+                    let rules = self.group_rules(start, c, 2)?;
+                    extend(&mut entry.entry_file_priority, file_priority(&self, &rules, &str_path)?)
+                }
+                _ => {
+                    let rules = self.group_rules(start, c, 2)?;
+                    extend(&mut entry.entry_exclude, exclude(&self, &rules, &str_path)?)
+                }
+            }
+        }
 
-                if let Some(p) = cap.get(3) {
-                    if let Ok(number) = p.as_str().parse::<usize>() {
-                        priority.priority = number;
-                    } else {
-                        return Err("Error parsing".to_string());
-                    }
-                } else {
-                    return Err("Error parsing".to_string());
+        Ok(entry)
+    }
+
+    /// Reads the path up to and including the `>` separator.
+    fn path(&mut self) -> Result<PathBuf, TemplateError> {
+        let leading = self.line.len() - self.line.trim_start().len();
+
+        if self.line[leading..].starts_with('"') {
+            self.position = leading;
+            let quoted = self.quoted()?;
+            self.skip(&[]);
+
+            if self.peek() != Some('>') {
+                return Err(self.error(self.position, self.rest(), "`>` after the quoted path"));
+            }
+
+            self.position += 1;
+            return Ok(path(&self.expand(leading + 1, &quoted)?));
+        }
+
+        let Some(separator) = self.line.find('>') else {
+            return Err(self.error(self.line.len(), self.line, "`>` between the path and the rule groups"));
+        };
+
+        self.position = separator + 1;
+
+        Ok(path(&self.expand(0, &self.line[..separator])?))
+    }
+
+    /// Reads the `{...}` rules of the group opened at `start` up to its `]`,
+    /// each rule made of at most `count` fields.
+    fn group_rules(&mut self, start: usize, group: char, count: usize) -> Result<Vec<Rule>, TemplateError> {
+        let mut rules = vec![];
+
+        loop {
+            self.skip(&[',']);
+
+            match self.peek() {
+                None => {
+                    return Err(self.error(start, &self.line[start..], &format!("`]` closing group {}", group)));
+                }
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(rules);
                 }
+                Some('{') => rules.push(self.rule(count)?),
+                Some(_) => {
+                    return Err(self.error(self.position, self.rest(), "`{` starting a rule or `]` closing the group"));
+                }
+            }
+        }
+    }
 
-                if let Some(content) = cap.get(4) {
-                    priority.content = content.as_str().to_string();
+    /// Reads a `{...}` rule of at most `count` comma separated fields, the
+    /// last one keeps any further commas.
+    fn rule(&mut self, count: usize) -> Result<Rule, TemplateError> {
+        let open = self.position;
+        let mut fields = vec![];
+
+        self.position += 1;
+
+        loop {
+            let last = fields.len() + 1 >= count;
+
+            fields.push(self.field(open, last)?);
+
+            match self.peek() {
+                Some('}') => {
+                    let close = self.position;
+                    self.position += 1;
+                    let predicates = self.predicates()?;
+                    return Ok(Rule { fields, close, predicates });
                 }
+                _ => self.position += 1,
+            }
+        }
+    }
+
+    /// Reads the comma separated predicates of a `(...)` right after a rule,
+    /// `{.*,,1}(size<2GB, age<7d)`.
+    fn predicates(&mut self) -> Result<Vec<Field>, TemplateError> {
+        let leading = self.rest().len() - self.rest().trim_start().len();
+
+        if !self.rest()[leading..].starts_with('(') {
+            return Ok(vec![]);
+        }
+
+        let open = self.position + leading;
+
+        let Some(length) = self.line[open..].find(')') else {
+            return Err(self.error(open, &self.line[open..], "`)` closing the predicates"));
+        };
+
+        let mut predicates = vec![];
+        let mut offset = open + 1;
+
+        for text in self.line[open + 1..open + length].split(',') {
+            predicates.push(Field {
+                offset,
+                text: text.to_string(),
+                quoted: false,
+            });
+            offset += text.len() + 1;
+        }
+
+        self.position = open + length + 1;
 
-                priority.root = path.clone();
+        Ok(predicates)
+    }
+
+    /// Reads one field, leaving the position on the `,` or `}` that ends it.
+    fn field(&mut self, open: usize, last: bool) -> Result<Field, TemplateError> {
+        let start = self.position;
+        let leading = self.rest().len() - self.rest().trim_start().len();
+
+        if self.rest()[leading..].starts_with('"') {
+            self.position += leading;
+
+            let offset = self.position;
+            let text = self.quoted()?;
+
+            self.skip(&[]);
+
+            return match self.peek() {
+                Some('}') => Ok(Field { offset, text, quoted: true }),
+                Some(',') if !last => Ok(Field { offset, text, quoted: true }),
+                _ => Err(self.error(self.position, self.rest(), if last { "`}` after the quoted field" } else { "`,` or `}` after the quoted field" })),
+            };
+        }
+
+        let Some(length) = field_length(self.rest(), last) else {
+            return Err(self.error(open, &self.line[open..], "`}` closing the rule"));
+        };
+
+        self.position += length;
+
+        Ok(Field {
+            offset: start,
+            text: self.line[start..self.position].to_string(),
+            quoted: false,
+        })
+    }
+
+    /// Reads a `"..."` string starting at the current position, `""` is an
+    /// escaped quote.
+    fn quoted(&mut self) -> Result<String, TemplateError> {
+        let open = self.position;
+        let mut text = String::new();
+
+        self.position += 1;
+
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error(open, &self.line[open..], "`\"` closing the quoted text"));
+            };
+
+            self.position += c.len_utf8();
+
+            if c != '"' {
+                text.push(c);
+                continue;
+            }
 
-                priorities.push(priority);
+            if self.peek() != Some('"') {
+                return Ok(text);
             }
+
+            text.push('"');
+            self.position += 1;
         }
     }
 
-    if priorities.is_empty() {
-        return Ok(None);
+    fn regex(&self, field: &Field) -> Result<Pattern, TemplateError> {
+        if field.text.is_empty() {
+            return Err(self.error(field.offset, &field.text, "a name pattern"));
+        }
+
+        let source = self.expand(field.offset + field.quoted as usize, &field.text)?;
+
+        Pattern::rule(&source).map_err(|error| self.error(field.offset, &source, &Pattern::expected(&error)))
     }
 
-    Ok(Some(priorities))
-}
+    fn content(&self, field: Option<&Field>) -> Result<Pattern, TemplateError> {
+        let Some(field) = field else {
+            return Ok(Pattern::default());
+        };
 
-fn dir_priority(line: &str, path: String) -> Result<Option<Vec<EntryDirPriority>>, String> {
-    let regex = Regex::new(r"3\[([^]]*)\]").unwrap();
-    let mut priorities = Vec::new();
+        if field.quoted {
+            return self.pattern(field.offset + 1, &field.text);
+        }
 
-    if let Some(captures) = regex.captures(line) {
-        if let Some(group) = captures.get(1) {
-            let group = group.as_str();
-            let regex = Regex::new(r"\{(.+?),\s*(\d*|\s*),\s*(\d+)\}").unwrap();
-
-            for cap in regex.captures_iter(group) {
-                let mut priority = EntryDirPriority::default();
-                if let Some(regex) = cap.get(1) {
-                    priority.regex = regex.as_str().to_string();
-                } else {
-                    return Err("Error parsing".to_string());
-                }
+        let trimmed = field.text.trim_start();
 
-                if let Some(deep) = cap.get(2) {
-                    if let Ok(number) = deep.as_str().parse::<usize>() {
-                        priority.deep = Some(number as i8);
-                    } else {
-                        priority.deep = None;
-                    }
-                } else {
-                    return Err("Error parsing".to_string());
-                }
+        self.pattern(field.offset + field.text.len() - trimmed.len(), trimmed)
+    }
 
-                if let Some(p) = cap.get(3) {
-                    if let Ok(number) = p.as_str().parse::<usize>() {
-                        priority.priority = number;
-                    } else {
-                        return Err("Error parsing".to_string());
-                    }
-                } else {
-                    return Err("Error parsing".to_string());
-                }
+    fn pattern(&self, offset: usize, source: &str) -> Result<Pattern, TemplateError> {
+        let source = self.expand(offset, source)?;
+
+        Pattern::content(&source).map_err(|error| self.error(offset, &source, &Pattern::expected(&error)))
+    }
+
+    fn deep(&self, field: Option<&Field>) -> Result<Option<i8>, TemplateError> {
+        let Some(field) = field else {
+            return Ok(None);
+        };
+
+        let trimmed = field.text.trim();
 
-                priority.root = path.clone();
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+
+        match trimmed.parse::<i8>() {
+            Ok(number) if number >= 0 => Ok(Some(number)),
+            _ => Err(self.error(field.offset + field.text.find(trimmed).unwrap_or(0), trimmed, "a depth number or nothing")),
+        }
+    }
+
+    fn file_predicates(&self, rule: &Rule) -> Result<Vec<FilePredicate>, TemplateError> {
+        rule.predicates
+            .iter()
+            .map(|field| FilePredicate::parse(&field.text).ok_or_else(|| self.error(field.offset, field.text.trim(), FilePredicate::EXPECTED)))
+            .collect()
+    }
+
+    /// Rule groups 3 and 5 also match directories, which have no size or age.
+    fn no_predicates(&self, rule: &Rule) -> Result<(), TemplateError> {
+        match rule.predicates.first() {
+            Some(field) => Err(self.error(field.offset, field.text.trim(), "no predicates, only rule groups 1, 2 and 4 take them")),
+            None => Ok(()),
+        }
+    }
+
+    fn priority(&self, rule: &Rule, field: Option<&Field>) -> Result<usize, TemplateError> {
+        let Some(field) = field else {
+            return Err(self.error(rule.close, "}", "a priority number"));
+        };
+
+        let trimmed = field.text.trim();
+
+        trimmed
+            .parse::<usize>()
+            .map_err(|_| self.error(field.offset + field.text.find(trimmed).unwrap_or(0), trimmed, "a priority number"))
+    }
+}
+
+fn extend<T>(target: &mut Option<Vec<T>>, items: Vec<T>) {
+    if items.is_empty() {
+        return;
+    }
+
+    target.get_or_insert_with(Vec::new).extend(items);
+}
+
+/// Length of an unquoted field, which ends at a `}` closing the rule or,
+/// unless it is the last one, at `,`. Braces of the regex itself, as in
+/// `a{2,3}`, nest and so do not end it, neither do escaped `\{`, `\}` or the
+/// `}` of a `${NAME}`.
+fn field_length(text: &str, last: bool) -> Option<usize> {
+    let mut index = 0;
+    let mut depth = 0;
+
+    while let Some(c) = text[index..].chars().next() {
+        if text[index..].starts_with("${") {
+            index += text[index..].find('}')? + 1;
+            continue;
+        }
 
-                priorities.push(priority);
+        match c {
+            '\\' => {
+                index += c.len_utf8();
+                index += text[index..].chars().next().map_or(0, char::len_utf8);
+                continue;
             }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            ',' if depth == 0 && !last => return Some(index),
+            _ => {}
         }
+
+        index += c.len_utf8();
     }
 
-    if priorities.is_empty() {
-        return Ok(None);
+    None
+}
+
+fn path(line: &str) -> PathBuf {
+    PathBuf::from(line)
+}
+
+/// `{regex, deep, content}(predicates)`, deep, content and predicates are optional.
+fn file_filter(parser: &LineParser, rules: &[Rule], path: &str) -> Result<Vec<EntryFileFilter>, TemplateError> {
+    let mut filters = Vec::new();
+
+    for rule in rules {
+        filters.push(EntryFileFilter {
+            regex: parser.regex(&rule.fields[0])?,
+            deep: parser.deep(rule.field(1))?,
+            content: parser.content(rule.field(2))?,
+            predicates: parser.file_predicates(rule)?,
+            root: path.to_string(),
+        });
     }
 
-    Ok(Some(priorities))
+    Ok(filters)
 }
 
-fn file_priority(line: &str, path: String) -> Result<Option<Vec<EntryFilePriority>>, String> {
-    let regex = Regex::new(r"4\[([^]]*)\]").unwrap();
+/// `{regex, deep, priority, content}(predicates)`, content and predicates are optional.
+fn dir_file_priority(parser: &LineParser, rules: &[Rule], path: &str) -> Result<Vec<EntryDirFilePriority>, TemplateError> {
     let mut priorities = Vec::new();
 
-    if let Some(captures) = regex.captures(line) {
-        if let Some(group) = captures.get(1) {
-            let group = group.as_str();
-            let regex = Regex::new(r"\{(\d+),*\s*(.*?)\}").unwrap();
+    for rule in rules {
+        priorities.push(EntryDirFilePriority {
+            regex: parser.regex(&rule.fields[0])?,
+            deep: parser.deep(rule.field(1))?,
+            priority: parser.priority(rule, rule.field(2))?,
+            content: parser.content(rule.field(3))?,
+            predicates: parser.file_predicates(rule)?,
+            root: path.to_string(),
+        });
+    }
 
-            for cap in regex.captures_iter(group) {
-                let mut priority = EntryFilePriority::default();
+    Ok(priorities)
+}
 
-                if let Some(p) = cap.get(1) {
-                    if let Ok(number) = p.as_str().parse::<usize>() {
-                        priority.priority = number;
-                    } else {
-                        return Err("Error parsing".to_string());
-                    }
-                } else {
-                    return Err("Error parsing".to_string());
-                }
+/// `{regex, deep, priority}`.
+fn dir_priority(parser: &LineParser, rules: &[Rule], path: &str) -> Result<Vec<EntryDirPriority>, TemplateError> {
+    let mut priorities = Vec::new();
 
-                if let Some(content) = cap.get(2) {
-                    priority.content = content.as_str().to_string();
-                }
+    for rule in rules {
+        parser.no_predicates(rule)?;
 
-                priority.root = path.clone();
+        priorities.push(EntryDirPriority {
+            regex: parser.regex(&rule.fields[0])?,
+            deep: parser.deep(rule.field(1))?,
+            priority: parser.priority(rule, rule.field(2))?,
+            root: path.to_string(),
+        });
+    }
 
-                priorities.push(priority);
-            }
-        }
+    Ok(priorities)
+}
+
+/// `{priority, content}(predicates)`, content and predicates are optional.
+fn file_priority(parser: &LineParser, rules: &[Rule], path: &str) -> Result<Vec<EntryFilePriority>, TemplateError> {
+    let mut priorities = Vec::new();
+
+    for rule in rules {
+        priorities.push(EntryFilePriority {
+            priority: parser.priority(rule, rule.field(0))?,
+            content: parser.content(rule.field(1))?,
+            predicates: parser.file_predicates(rule)?,
+            root: path.to_string(),
+        });
     }
 
-    if priorities.is_empty() {
-        return Ok(None);
+    Ok(priorities)
+}
+
+/// `{regex, deep}`, deep is optional.
+fn exclude(parser: &LineParser, rules: &[Rule], path: &str) -> Result<Vec<EntryExclude>, TemplateError> {
+    let mut excludes = Vec::new();
+
+    for rule in rules {
+        parser.no_predicates(rule)?;
+
+        excludes.push(EntryExclude {
+            regex: parser.regex(&rule.fields[0])?,
+            deep: parser.deep(rule.field(1))?,
+            root: path.to_string(),
+        });
     }
 
-    Ok(Some(priorities))
+    Ok(excludes)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::mappers::template_to_dir_entry;
+    use crate::models::dir_entry::DirEntry;
+    use crate::models::template_error::TemplateError;
+
+    fn entry(line: &str) -> DirEntry {
+        template_to_dir_entry::map(line.to_string(), None).unwrap().remove(0)
+    }
+
+    fn paths(template: &str, origin: Option<&Path>) -> Vec<String> {
+        template_to_dir_entry::map(template.to_string(), origin)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path_to_string())
+            .collect()
+    }
+
+    fn error(template: &str, origin: Option<&Path>) -> TemplateError {
+        template_to_dir_entry::map(template.to_string(), origin).unwrap_err()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watcher_backup_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn reads_every_group_of_the_original_format() {
+        let entry = entry(r"/home/user>1[{\.txt$,2,secret}{\.md$,}]2[{\.rs$,1,3,fn main}]3[{^src$,,2}]4[{5,password}]s");
+
+        assert_eq!(entry.path.to_str(), Some("/home/user"));
+        assert!(entry.selected);
+
+        let filters = entry.entry_file_filter.unwrap();
+        assert_eq!((filters[0].regex.to_string(), filters[0].deep, filters[0].content.to_string()), (r"\.txt$".to_string(), Some(2), "secret".to_string()));
+        assert_eq!((filters[1].regex.to_string(), filters[1].deep, filters[1].content.is_empty()), (r"\.md$".to_string(), None, true));
+
+        let dir_file = &entry.entry_dir_file_priority.unwrap()[0];
+        assert_eq!((dir_file.regex.to_string(), dir_file.deep, dir_file.priority, dir_file.content.to_string()), (r"\.rs$".to_string(), Some(1), 3, "fn main".to_string()));
+
+        let dir = &entry.entry_dir_priority.unwrap()[0];
+        assert_eq!((dir.regex.to_string(), dir.deep, dir.priority), ("^src$".to_string(), None, 2));
+
+        let file = &entry.entry_file_priority.unwrap()[0];
+        assert_eq!((file.priority, file.content.to_string()), (5, "password".to_string()));
+    }
+
+    #[test]
+    fn braces_of_a_regex_stay_in_the_field() {
+        let filter = &entry(r"C:\x>1[{a{2},}]s").entry_file_filter.unwrap()[0];
+        assert_eq!((filter.regex.to_string(), filter.deep), ("a{2}".to_string(), None));
+
+        let filter = &entry(r"/data>1[{\d{2,3},1}]").entry_file_filter.unwrap()[0];
+        assert_eq!((filter.regex.to_string(), filter.deep), (r"\d{2,3}".to_string(), Some(1)));
+
+        let filter = &entry(r"/data>1[{\{,,x{1,}}]").entry_file_filter.unwrap()[0];
+        assert_eq!((filter.regex.to_string(), filter.content.to_string()), (r"\{".to_string(), "x{1,}".to_string()));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_fragment() {
+        for (template, column, fragment, expected) in [
+            (r"/data1[{a,}]", 13, r"/data1[{a,}]", "`>` between the path and the rule groups"),
+            (r"/data>1[{a{2,}]", 9, r"{a{2,}]", "`}` closing the rule"),
+            (r"/data>2[{.*,,high}]", 14, "high", "a priority number"),
+        ] {
+            let error = error(template, None);
+
+            assert_eq!((error.line, error.column, error.fragment.as_str(), error.expected.as_str()), (1, column, fragment, expected), "{}", template);
+        }
+    }
+
+    #[test]
+    fn finds_line_only_directives() {
+        assert!(template_to_dir_entry::has_directives("include base.tpl\n/data>s"));
+        assert!(template_to_dir_entry::has_directives("set ROOT = /data\n${ROOT}>s"));
+        assert!(template_to_dir_entry::has_directives("${HOME}/docs>s"));
+        assert!(!template_to_dir_entry::has_directives("# include base.tpl\n/data/$${literal}>s"));
+    }
+
+    #[test]
+    fn expands_set_and_environment_variables() {
+        std::env::set_var("WATCHER_BACKUP_TEST_DRIVE", "/mnt/usb");
+        std::env::set_var("WATCHER_BACKUP_TEST_SHADOWED", "/from/environment");
+
+        assert_eq!(
+            paths(
+                "set ROOT = /data\nset DOCS = ${ROOT}/docs\n${DOCS}>s\n${WATCHER_BACKUP_TEST_DRIVE}/photos>s\nset WATCHER_BACKUP_TEST_SHADOWED = /from/set\n${WATCHER_BACKUP_TEST_SHADOWED}>s",
+                None
+            ),
+            ["/data/docs", "/mnt/usb/photos", "/from/set"]
+        );
+        assert_eq!(entry(r"/data>1[{${WATCHER_BACKUP_TEST_DRIVE},}]").entry_file_filter.unwrap()[0].regex.to_string(), "/mnt/usb");
+    }
+
+    #[test]
+    fn doubled_dollar_is_a_literal_variable() {
+        assert_eq!(paths("/data/$${ROOT}>s", None), ["/data/${ROOT}"]);
+        assert_eq!(paths("set ROOT = /data\n$${ROOT}/${ROOT}>s", None), ["${ROOT}//data"]);
+    }
+
+    #[test]
+    fn undefined_variables_are_errors() {
+        let undefined = "a variable defined with `set NAME = value` or in the environment";
+
+        for (template, line, column, fragment) in [
+            ("/data/${WATCHER_BACKUP_TEST_UNDEFINED}>s", 1, 7, "${WATCHER_BACKUP_TEST_UNDEFINED}"),
+            ("/data>s\n/data>1[{${NOPE_42},}]", 2, 10, "${NOPE_42}"),
+            ("set ROOT = ${NOPE_42}/data", 1, 12, "${NOPE_42}"),
+            ("/data/${UNCLOSED>s", 1, 7, "${UNCLOSED"),
+        ] {
+            let error = error(template, None);
+
+            assert_eq!((error.line, error.column, error.fragment.as_str(), error.expected.as_str()), (line, column, fragment, undefined), "{}", template);
+        }
+    }
+
+    #[test]
+    fn includes_are_relative_to_the_including_template() {
+        let dir = temp_dir("template_includes");
+
+        fs::create_dir_all(dir.join("parts/more")).unwrap();
+        fs::write(dir.join("main.tpl"), "").unwrap();
+        fs::write(dir.join("parts/base.tpl"), "set ROOT = /data\n${ROOT}/base>s\ninclude more/extra.tpl").unwrap();
+        fs::write(dir.join("parts/more/extra.tpl"), "${ROOT}/extra>s").unwrap();
+
+        assert_eq!(
+            paths("/first>s\ninclude parts/base.tpl\n${ROOT}/last>s", Some(&dir.join("main.tpl"))),
+            ["/first", "/data/base", "/data/extra", "/data/last"]
+        );
+
+        let lines = template_to_dir_entry::parse_lines("/first>s\ninclude parts/base.tpl", Some(&dir.join("main.tpl")))
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect::<Vec<usize>>();
+
+        assert_eq!(lines, [1, 2, 2]);
+
+        let missing = error("include parts/extra.tpl", Some(&dir.join("main.tpl")));
+
+        assert_eq!((missing.line, missing.column, missing.fragment.as_str()), (1, 9, "parts/extra.tpl"));
+        assert!(missing.expected.starts_with("a readable template"), "{}", missing.expected);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let dir = temp_dir("template_include_cycle");
+
+        fs::write(dir.join("a.tpl"), "include b.tpl").unwrap();
+        fs::write(dir.join("b.tpl"), "/data>s\ninclude a.tpl").unwrap();
+
+        let a = dir.join("a.tpl").canonicalize().unwrap();
+        let b = dir.join("b.tpl").canonicalize().unwrap();
+        let cycle = error("include b.tpl", Some(&dir.join("a.tpl")));
+
+        assert_eq!((cycle.line, cycle.column), (2, 9));
+        assert_eq!(cycle.fragment, format!("{} -> {} -> {}", a.display(), b.display(), a.display()));
+        assert_eq!(cycle.expected, "an include that does not lead back to a template being read");
+        assert_eq!(cycle.file, Some(dir.join("b.tpl").display().to_string()));
+
+        let itself = error("include b.tpl", Some(&dir.join("b.tpl")));
+
+        assert_eq!((itself.line, itself.file), (1, None));
+        assert_eq!(itself.fragment, format!("{} -> {}", b.display(), b.display()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
\ No newline at end of file
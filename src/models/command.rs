@@ -1,38 +1,36 @@
 use std::hash::Hash;
-#[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
 use serde::Serialize;
 
-#[derive(Debug, Eq, Serialize)]
+#[derive(Clone, Debug, Eq, Serialize)]
 pub struct Command {
     pub local_path: String,
     pub remote_path: String,
     pub priority: Option<usize>,
+    /// A symlink met while walking under `LinkPolicy::Record`, copied as the
+    /// link itself. Links written in the template are never recorded.
+    pub link: bool,
 }
 
 impl Command {
-    #[cfg(target_os = "windows")]
-    pub fn spawn_rclone_command(&self, cloud: &str, root: &str, speed: f32) {
-        let _ = std::process::Command::new("rclone")
-            .arg("copy")
-            .arg("--bwlimit")
-            .arg(format!("{}K", speed.to_string()))
-            .arg(&self.local_path)
-            .arg(format!("{}:{}\\{}", cloud, root, self.remote_path))
-            .creation_flags(0x08000000)
-            .output();
+    /// Splits `remote_path` into its non-empty segments, whichever separator
+    /// the local OS produced.
+    pub fn remote_segments(&self) -> impl Iterator<Item = &str> {
+        self.remote_path
+            .split(['/', '\\'])
+            .filter(|part| !part.is_empty())
     }
 
-    #[cfg(target_os = "macos")]
-    pub fn spawn_rclone_command(&self, cloud: &str, root: &str, speed: f32) {
-        let _ = std::process::Command::new("rclone")
-            .arg("copy")
-            .arg("--bwlimit")
-            .arg(format!("{}K", speed.to_string()))
-            .arg(&self.local_path)
-            .arg(format!("{}:{}{}", cloud, root, self.remote_path))
-            .output();
+    /// Builds `cloud:root/remote/path`, always joined with `/` since rclone
+    /// remotes use POSIX separators whatever the local OS is.
+    pub fn remote_target(&self, cloud: &str, root: &str) -> String {
+        let remote_path = self.remote_segments().collect::<Vec<&str>>().join("/");
 
+        let root = root.trim_end_matches(['/', '\\']);
+
+        match remote_path.is_empty() {
+            true => format!("{}:{}", cloud, root),
+            false => format!("{}:{}/{}", cloud, root, remote_path),
+        }
     }
 }
 
@@ -47,4 +45,4 @@ impl PartialEq for Command {
     fn eq(&self, other: &Self) -> bool {
         self.local_path == other.local_path && self.remote_path == other.remote_path
     }
-}
\ No newline at end of file
+}
@@ -4,6 +4,9 @@ use serde::{Deserialize, Serialize};
 pub struct CloudConfig {
     pub mega: String,
     pub google_drive: String,
+    /// Directory used by `Cloud::Local`, e.g. a mounted NAS share or USB drive.
+    #[serde(default)]
+    pub local: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// How content rules read the files they are matched against.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentPolicy {
+    /// Only the first `max_bytes` of a file are matched, 1 MiB by default and
+    /// not capped. They are read into memory whole, so a scan may hold up to
+    /// `scan_threads` times this much at once.
+    pub max_bytes: u64,
+    pub binary: BinaryContent,
+}
+
+/// What content rules do with files holding NUL bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryContent {
+    /// Never match binary files.
+    #[default]
+    Skip,
+    /// Match against the raw bytes.
+    Raw,
+    /// Match against the runs of printable ASCII, one per line, like `strings`.
+    Strings,
+}
+
+impl Default for ContentPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            binary: BinaryContent::Skip,
+        }
+    }
+}
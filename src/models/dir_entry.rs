@@ -3,11 +3,14 @@ use std::path::PathBuf;
 
 use serde::Serialize;
 
+use crate::filesystems::file_system::FileSystem;
+
 use super::{
     entry_file_filter::EntryFileFilter,
     entry_dir_file_priority::EntryDirFilePriority,
     entry_dir_priority::EntryDirPriority,
     entry_file_priority::EntryFilePriority,
+    entry_exclude::EntryExclude,
 };
 
 
@@ -20,6 +23,7 @@ pub struct DirEntry {
     pub entry_dir_file_priority: Option<Vec<EntryDirFilePriority>>,
     pub entry_dir_priority: Option<Vec<EntryDirPriority>>,
     pub entry_file_priority: Option<Vec<EntryFilePriority>>,
+    pub entry_exclude: Option<Vec<EntryExclude>>,
 }
 
 
@@ -32,25 +36,12 @@ impl DirEntry {
         self.path.components().count()
     }
 
-    pub fn is_dir(&self) -> bool {
-        self.path.is_dir()
-    }
-
-    pub fn is_file(&self) -> bool {
-        !self.is_dir()
-    }
-
-    pub fn short_name(&self) -> String {
-        let file_name = self.path.file_name().and_then(|name| name.to_str());
-
-        match file_name {
-            Some(name) => name.to_string(),
-            None => self.path.to_string_lossy().to_string(),
-        }
+    pub fn is_file(&self, fs: &dyn FileSystem) -> bool {
+        fs.is_file(&self.path)
     }
 
     pub fn is_just_selected(&self) -> bool {
-        self.selected && self.entry_file_filter == None && self.entry_file_priority == None && self.entry_dir_file_priority == None
-            && self.entry_dir_priority == None
+        self.selected && self.entry_file_filter.is_none() && self.entry_file_priority.is_none() && self.entry_dir_file_priority.is_none()
+            && self.entry_dir_priority.is_none()
     }
 }
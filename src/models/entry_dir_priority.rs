@@ -1,8 +1,10 @@
 use serde::Serialize;
 
+use super::pattern::Pattern;
+
 #[derive(Default, Clone, PartialEq, Eq, Debug, Serialize)]
 pub struct EntryDirPriority {
-    pub regex: String,
+    pub regex: Pattern,
     pub deep: Option<i8>,
     pub priority: usize,
     pub root: String,
@@ -0,0 +1,11 @@
+use serde::Serialize;
+
+use super::pattern::Pattern;
+
+/// Drops the files and whole directories whose name matches `regex`.
+#[derive(Default, Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct EntryExclude {
+    pub regex: Pattern,
+    pub deep: Option<i8>,
+    pub root: String,
+}
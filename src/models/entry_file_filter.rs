@@ -1,10 +1,13 @@
-use std::usize;
-
 use serde::Serialize;
 
+use super::file_predicate::FilePredicate;
+use super::pattern::Pattern;
+
 #[derive(Default, Clone, PartialEq, Eq, Debug, Serialize)]
 pub struct EntryFileFilter {
-    pub regex: String,
-    pub content: String,
+    pub regex: Pattern,
+    pub content: Pattern,
+    pub predicates: Vec<FilePredicate>,
     pub deep: Option<i8>,
+    pub root: String,
 }
@@ -1,8 +1,12 @@
 use serde::Serialize;
 
+use super::file_predicate::FilePredicate;
+use super::pattern::Pattern;
+
 #[derive(Default, Clone, PartialEq, Eq, Debug, Serialize)]
 pub struct EntryFilePriority {
-    pub content: String,
+    pub content: Pattern,
+    pub predicates: Vec<FilePredicate>,
     pub priority: usize,
     pub root: String,
 }
@@ -0,0 +1,16 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// One thing the planner did with the explained path or one of its
+/// ancestors, in the order it happened.
+#[derive(Clone, Debug)]
+pub struct ExplainStep {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ExplainStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
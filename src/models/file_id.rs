@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+
+/// Identifies a file or directory whichever path reaches it: its device and
+/// inode where the platform has them, its canonical path elsewhere.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FileId {
+    Inode { device: u64, inode: u64 },
+    Path(PathBuf),
+}
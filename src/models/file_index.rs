@@ -0,0 +1,24 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub size: u64,
+    /// Modification time in milliseconds since the Unix epoch.
+    pub modified: i64,
+    pub hash: Option<String>,
+}
+
+impl FileState {
+    pub fn same_metadata(&self, other: &FileState) -> bool {
+        self.size == other.size && self.modified == other.modified
+    }
+}
+
+/// State of every file as of its last successful upload.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FileIndex {
+    pub runs_since_full: u32,
+    pub files: HashMap<String, FileState>,
+}
@@ -0,0 +1,10 @@
+/// What a path is on disk, without following a final symlink.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Missing,
+    File,
+    Dir,
+    Symlink,
+    /// Sockets, FIFOs and device nodes.
+    Special,
+}
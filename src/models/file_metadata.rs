@@ -0,0 +1,8 @@
+use std::time::SystemTime;
+
+/// What rule predicates need to know about a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
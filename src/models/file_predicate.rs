@@ -0,0 +1,225 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::NaiveDate;
+use serde::{Serialize, Serializer};
+
+use super::file_metadata::FileMetadata;
+
+/// A condition on a file's metadata, `size>2GB`, `age<7d` or
+/// `modified>=2024-01-01`. Sizes are 1024 based (`B`, `KB`, `MB`, `GB`,
+/// `TB`), ages take `s`, `m`, `h`, `d`, `w` or `y` (365 days) and dates are
+/// UTC midnight. Compares and serializes as its source text.
+#[derive(Clone)]
+pub struct FilePredicate {
+    source: String,
+    subject: Subject,
+    operator: Operator,
+    value: i64,
+}
+
+#[derive(Clone, Copy)]
+enum Subject {
+    Size,
+    Age,
+    Modified,
+}
+
+#[derive(Clone, Copy)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+const SIZE_UNITS: [(&str, i64); 5] = [("TB", 1 << 40), ("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10), ("B", 1)];
+const AGE_UNITS: [(&str, i64); 6] = [("s", 1), ("m", 60), ("h", 3600), ("d", 86_400), ("w", 604_800), ("y", 31_536_000)];
+
+impl FilePredicate {
+    /// What a template error expects when `FilePredicate::parse` fails.
+    pub const EXPECTED: &'static str = "a predicate like `size>2GB`, `age<7d` or `modified>=2024-01-01`";
+
+    pub fn parse(source: &str) -> Option<Self> {
+        let text = source.trim();
+
+        let (subject, rest) = [("size", Subject::Size), ("age", Subject::Age), ("modified", Subject::Modified)]
+            .into_iter()
+            .find_map(|(name, subject)| text.strip_prefix(name).map(|rest| (subject, rest.trim_start())))?;
+
+        let (operator, rest) = [("<=", Operator::LessOrEqual), (">=", Operator::GreaterOrEqual), ("<", Operator::Less), (">", Operator::Greater)]
+            .into_iter()
+            .find_map(|(symbol, operator)| rest.strip_prefix(symbol).map(|rest| (operator, rest.trim())))?;
+
+        let value = match subject {
+            Subject::Size => amount(rest, &SIZE_UNITS, true)?,
+            Subject::Age => amount(rest, &AGE_UNITS, false)?,
+            Subject::Modified => NaiveDate::parse_from_str(rest, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)?
+                .and_utc()
+                .timestamp(),
+        };
+
+        Some(Self {
+            source: text.to_string(),
+            subject,
+            operator,
+            value,
+        })
+    }
+
+    /// Whether a file with `metadata` satisfies the predicate at `now`.
+    /// Files without a modification time never match age and date predicates.
+    pub fn is_match(&self, metadata: &FileMetadata, now: SystemTime) -> bool {
+        let actual = match self.subject {
+            Subject::Size => metadata.size as i64,
+            Subject::Age => match metadata.modified.and_then(|modified| now.duration_since(modified).ok()) {
+                Some(age) => age.as_secs() as i64,
+                None => return false,
+            },
+            Subject::Modified => match metadata.modified.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()) {
+                Some(modified) => modified.as_secs() as i64,
+                None => return false,
+            },
+        };
+
+        match self.operator {
+            Operator::Less => actual < self.value,
+            Operator::LessOrEqual => actual <= self.value,
+            Operator::Greater => actual > self.value,
+            Operator::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+/// Parses `12GB` or `7d` against `units`, a bare number is only allowed when
+/// `bare` is set and counts the smallest unit.
+fn amount(text: &str, units: &[(&str, i64)], bare: bool) -> Option<i64> {
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number = number.parse::<i64>().ok()?;
+
+    let multiplier = match unit.trim() {
+        "" if bare => 1,
+        unit => units
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(unit) || (name.len() == 2 && unit.eq_ignore_ascii_case(&name[..1])))
+            .map(|(_, multiplier)| *multiplier)?,
+    };
+
+    number.checked_mul(multiplier)
+}
+
+impl PartialEq for FilePredicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for FilePredicate {}
+
+impl fmt::Debug for FilePredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+impl fmt::Display for FilePredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for FilePredicate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::models::file_metadata::FileMetadata;
+    use crate::models::file_predicate::FilePredicate;
+
+    const DAY: u64 = 86_400;
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(20_000 * DAY)
+    }
+
+    fn file(size: u64, age_days: Option<u64>) -> FileMetadata {
+        FileMetadata {
+            size,
+            modified: age_days.map(|days| now() - Duration::from_secs(days * DAY)),
+        }
+    }
+
+    fn matches(source: &str, metadata: FileMetadata) -> bool {
+        FilePredicate::parse(source).unwrap().is_match(&metadata, now())
+    }
+
+    #[test]
+    fn sizes_take_units_and_their_shorthand() {
+        for (source, size, matched) in [
+            ("size>2GB", 2 << 30, false),
+            ("size>2GB", (2 << 30) + 1, true),
+            ("size>=2G", 2 << 30, true),
+            ("size<1kb", 1023, true),
+            ("size<1K", 1024, false),
+            ("size<=3MB", 3 << 20, true),
+            ("size>1T", 1 << 40, false),
+            ("size<10B", 9, true),
+            ("size<10", 9, true),
+            ("size < 10 KB", 10 << 10, false),
+        ] {
+            assert_eq!(matches(source, file(size, Some(1))), matched, "{} with {} bytes", source, size);
+        }
+
+        for source in ["size>2XB", "size>GB", "size=2GB", "size>-1", "weight>2GB", "size>99999999999TB"] {
+            assert!(FilePredicate::parse(source).is_none(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn ages_need_a_unit() {
+        assert!(FilePredicate::parse("age<7").is_none());
+        assert!(FilePredicate::parse("age<7B").is_none());
+        assert!(matches("age<7d", file(0, Some(6))));
+        assert!(!matches("age<7d", file(0, Some(7))));
+        assert!(matches("age>=1w", file(0, Some(7))));
+        assert!(matches("age>2y", file(0, Some(731))));
+        assert!(matches("age<90m", file(0, Some(0))));
+        assert!(matches("age>23h", file(0, Some(1))));
+    }
+
+    #[test]
+    fn modified_compares_with_utc_midnight() {
+        let midnight = UNIX_EPOCH + Duration::from_secs(19_723 * DAY);
+        let at = |modified: SystemTime| FileMetadata { size: 0, modified: Some(modified) };
+        let predicate = FilePredicate::parse("modified>=2024-01-01").unwrap();
+
+        assert!(predicate.is_match(&at(midnight), now()));
+        assert!(!predicate.is_match(&at(midnight - Duration::from_secs(1)), now()));
+        assert!(FilePredicate::parse("modified<2024-01-01").unwrap().is_match(&at(midnight - Duration::from_secs(1)), now()));
+        assert!(FilePredicate::parse("modified>=2024-13-01").is_none());
+        assert!(FilePredicate::parse("modified>=yesterday").is_none());
+    }
+
+    #[test]
+    fn files_without_a_modification_time_only_match_sizes() {
+        assert!(!matches("age<7d", file(10, None)));
+        assert!(!matches("age>7d", file(10, None)));
+        assert!(!matches("modified>=2024-01-01", file(10, None)));
+        assert!(!matches("modified<2024-01-01", file(10, None)));
+        assert!(matches("size<1KB", file(10, None)));
+    }
+
+    #[test]
+    fn compares_by_source_text() {
+        assert_eq!(FilePredicate::parse(" size>2GB ").unwrap().to_string(), "size>2GB");
+        assert_ne!(FilePredicate::parse("size>2GB"), FilePredicate::parse("size>2G"));
+    }
+}
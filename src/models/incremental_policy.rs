@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use super::file_index::FileIndex;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IncrementalPolicy {
+    /// Only upload files that changed since the last successful upload.
+    pub enabled: bool,
+    /// Force a full backup every N runs, 0 never forces one.
+    pub full_every: u32,
+    /// Compare content hashes when size or modification time differ, so
+    /// touched but unchanged files are not uploaded again.
+    pub hash: bool,
+}
+
+impl IncrementalPolicy {
+    /// Whether the next run uploads every file. The first run against an
+    /// empty `index` always does, so every chain starts from a full snapshot.
+    pub fn is_full_run(&self, index: &FileIndex) -> bool {
+        !self.enabled || index.files.is_empty() || (self.full_every > 0 && index.runs_since_full + 1 >= self.full_every)
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// What the walk does with the symlinks, and junctions on Windows, it finds
+/// below a template entry. Paths written in the template are always followed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkPolicy {
+    /// Leave links out of the backup.
+    Skip,
+    /// Back up what links point to. A directory link leading back to one of
+    /// its own ancestors is not entered.
+    #[default]
+    Follow,
+    /// Back up the link itself instead of its target.
+    Record,
+}
@@ -5,4 +5,24 @@ pub mod entry_file_filter;
 pub mod entry_file_priority;
 pub mod command;
 pub mod scheduler;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod plan_entry;
+pub mod transfer_outcome;
+pub mod retry_policy;
+pub mod transfer_report;
+pub mod run_journal;
+pub mod incremental_policy;
+pub mod file_index;
+pub mod retention_policy;
+pub mod template_error;
+pub mod pattern;
+pub mod template_issue;
+pub mod template_document;
+pub mod entry_exclude;
+pub mod file_predicate;
+pub mod file_metadata;
+pub mod content_policy;
+pub mod link_policy;
+pub mod file_kind;
+pub mod file_id;
+pub mod explain_step;
\ No newline at end of file
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::path::Path;
+
+use regex::{bytes, Regex};
+use serde::{Serialize, Serializer};
+
+/// A rule regex compiled once when the template is loaded. Compares and
+/// serializes as its source text.
+#[derive(Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Matcher,
+    /// Matched against the path relative to the rule's root instead of the name.
+    relative: bool,
+}
+
+/// Name patterns match text, content patterns the bytes of a file.
+#[derive(Clone)]
+enum Matcher {
+    Text(Regex),
+    Bytes(bytes::Regex),
+}
+
+impl Matcher {
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Text(regex) => regex.is_match(text),
+            Matcher::Bytes(regex) => regex.is_match(text.as_bytes()),
+        }
+    }
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            source: source.to_string(),
+            regex: Matcher::Text(Regex::new(source)?),
+            relative: false,
+        })
+    }
+
+    /// A content pattern, matched against the bytes of a file so non UTF-8
+    /// text still matches. `(?-u:\xFF)` matches a raw byte.
+    pub fn content(source: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            source: source.to_string(),
+            regex: Matcher::Bytes(bytes::Regex::new(source)?),
+            relative: false,
+        })
+    }
+
+    /// A name pattern of a rule, a regex unless marked `glob:` (`glob:*.pdf`).
+    /// `re:` marks a regex explicitly. A glob containing `/` matches the path
+    /// relative to the rule's root, `glob:reports/**/*.pdf`.
+    pub fn rule(source: &str) -> Result<Self, regex::Error> {
+        if let Some(glob) = source.strip_prefix("glob:") {
+            return Ok(Self {
+                source: source.to_string(),
+                regex: Matcher::Text(Regex::new(&glob_to_regex(glob))?),
+                relative: glob.contains('/'),
+            });
+        }
+
+        let mut pattern = Self::new(source.strip_prefix("re:").unwrap_or(source))?;
+        pattern.source = source.to_string();
+
+        Ok(pattern)
+    }
+
+    /// What a template error expects when `Pattern::new` fails, keeping only
+    /// the last line of the regex error.
+    pub fn expected(error: &regex::Error) -> String {
+        let reason = error.to_string();
+        let reason = reason.lines().last().unwrap_or_default().trim().trim_start_matches("error: ");
+
+        format!("a valid pattern ({})", reason)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    pub fn is_match_bytes(&self, data: &[u8]) -> bool {
+        match &self.regex {
+            Matcher::Bytes(regex) => regex.is_match(data),
+            Matcher::Text(regex) => std::str::from_utf8(data).is_ok_and(|text| regex.is_match(text)),
+        }
+    }
+
+    /// Matches a `/`-separated path relative to the rule's root, or only its
+    /// last segment for name patterns.
+    pub fn matches_relative(&self, relative: &str) -> bool {
+        match self.relative {
+            true => self.regex.is_match(relative),
+            false => self.regex.is_match(relative.rsplit('/').next().unwrap_or_default()),
+        }
+    }
+
+    /// Matches the entry at `path` for a rule declared on `root`.
+    pub fn matches_path(&self, root: &Path, path: &Path) -> bool {
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) => relative,
+            Err(_) => path,
+        };
+
+        let relative = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        self.matches_relative(&relative)
+    }
+}
+
+/// Translates a glob to an anchored regex: `*` and `?` stay within one
+/// segment, `**` crosses segments, `[!a-z]` negates a class and `{a,b}`
+/// picks an alternative.
+fn glob_to_regex(glob: &str) -> String {
+    let chars = glob.chars().collect::<Vec<char>>();
+    let mut regex = String::from("^");
+    let mut alternatives = 0;
+    let mut index = 0;
+
+    while index < chars.len() {
+        match chars[index] {
+            '*' if chars.get(index + 1) == Some(&'*') => {
+                index += 1;
+
+                match chars.get(index + 1) == Some(&'/') {
+                    true => {
+                        index += 1;
+                        regex.push_str("(?:.*/)?");
+                    }
+                    false => regex.push_str(".*"),
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[index + 1..].iter().skip(1).position(|c| *c == ']') {
+                Some(length) => {
+                    let class = chars[index + 1..index + 2 + length].iter().collect::<String>();
+                    let class = class.strip_prefix('!').map(|class| format!("^{}", class)).unwrap_or(class);
+
+                    regex.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+                    index += length + 2;
+                }
+                None => regex.push_str("\\["),
+            },
+            '{' => {
+                alternatives += 1;
+                regex.push_str("(?:");
+            }
+            ',' if alternatives > 0 => regex.push('|'),
+            '}' if alternatives > 0 => {
+                alternatives -= 1;
+                regex.push(')');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+
+        index += 1;
+    }
+
+    regex.push('$');
+    regex
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self::new("").unwrap()
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Pattern {}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::models::pattern::Pattern;
+
+    fn matches(source: &str, relative: &str) -> bool {
+        Pattern::rule(source).unwrap().matches_relative(relative)
+    }
+
+    #[test]
+    fn star_and_question_mark_stay_within_a_segment() {
+        assert!(matches("glob:*.pdf", "a.pdf"));
+        assert!(matches("glob:*.pdf", ".pdf"));
+        assert!(!matches("glob:*.pdf", "a.pdf.bak"));
+        assert!(matches("glob:docs/*.pdf", "docs/a.pdf"));
+        assert!(!matches("glob:docs/*.pdf", "docs/2024/a.pdf"));
+        assert!(matches("glob:?.txt", "a.txt"));
+        assert!(!matches("glob:?.txt", "ab.txt"));
+        assert!(!matches("glob:docs?a.txt", "docs/a.txt"));
+    }
+
+    #[test]
+    fn double_star_crosses_segments() {
+        assert!(matches("glob:reports/**/*.pdf", "reports/a.pdf"));
+        assert!(matches("glob:reports/**/*.pdf", "reports/2024/q1/a.pdf"));
+        assert!(!matches("glob:reports/**/*.pdf", "old/reports/a.pdf"));
+        assert!(matches("glob:**/*.rs", "main.rs"));
+        assert!(matches("glob:**/*.rs", "src/deep/mod.rs"));
+        assert!(matches("glob:src/**", "src/deep/mod.rs"));
+    }
+
+    #[test]
+    fn classes_and_alternatives() {
+        assert!(matches("glob:file[0-9].txt", "file7.txt"));
+        assert!(!matches("glob:file[0-9].txt", "fileA.txt"));
+        assert!(matches("glob:[!.]*", "notes"));
+        assert!(!matches("glob:[!.]*", ".hidden"));
+        assert!(matches("glob:a[b", "a[b"));
+        assert!(matches("glob:*.{jpg,png}", "a.jpg"));
+        assert!(matches("glob:*.{jpg,png}", "a.png"));
+        assert!(!matches("glob:*.{jpg,png}", "a.gif"));
+        assert!(matches("glob:{src,docs}/*.md", "docs/readme.md"));
+        assert!(matches("glob:a,b", "a,b"));
+        assert!(matches("glob:a.b", "a.b"));
+        assert!(!matches("glob:a.b", "axb"));
+    }
+
+    #[test]
+    fn globs_with_a_slash_match_the_relative_path_others_the_name() {
+        let root = Path::new("/data");
+
+        assert!(Pattern::rule("glob:*.pdf").unwrap().matches_path(root, Path::new("/data/reports/2024/a.pdf")));
+        assert!(Pattern::rule("glob:reports/**/*.pdf").unwrap().matches_path(root, Path::new("/data/reports/2024/a.pdf")));
+        assert!(!Pattern::rule("glob:2024/*.pdf").unwrap().matches_path(root, Path::new("/data/reports/2024/a.pdf")));
+        assert!(Pattern::rule(r"^a\.pdf$").unwrap().matches_path(root, Path::new("/data/reports/a.pdf")));
+        assert!(!Pattern::rule("re:^reports").unwrap().matches_path(root, Path::new("/data/reports/a.pdf")));
+        assert!(Pattern::rule("re:^reports").unwrap().matches_path(root, Path::new("/data/reports")));
+        assert_eq!(Pattern::rule("re:^reports").unwrap().to_string(), "re:^reports");
+    }
+}
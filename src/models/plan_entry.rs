@@ -0,0 +1,11 @@
+use serde::Serialize;
+
+use super::scheduler::Cloud;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlanEntry {
+    pub cloud: Cloud,
+    pub local_path: String,
+    pub remote_path: String,
+    pub priority: Option<usize>,
+}
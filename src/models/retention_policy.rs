@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// Which snapshot roots to keep on each cloud. A snapshot is kept when any
+/// `keep_*` rule selects it, `max_age_days` then removes everything older.
+/// The newest snapshot is never pruned.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub keep_daily: Option<usize>,
+    pub keep_weekly: Option<usize>,
+    pub keep_monthly: Option<usize>,
+    pub max_age_days: Option<i64>,
+    /// Only report what would be pruned.
+    pub dry_run: bool,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.has_keep_rules() || self.max_age_days.is_some()
+    }
+
+    pub fn has_keep_rules(&self) -> bool {
+        self.keep_last.is_some() || self.keep_daily.is_some() || self.keep_weekly.is_some() || self.keep_monthly.is_some()
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::scheduler::Cloud;
+
+/// One line of the on-disk journal, the file is append-only JSON lines so an
+/// interrupted run loses at most the record being written.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum JournalRecord {
+    Start { root: String, started_at: i64 },
+    /// `target` is where the file went, so a command whose remote path
+    /// changed since the interrupted run is uploaded again.
+    Completed {
+        cloud: Cloud,
+        local_path: String,
+        target: String,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RunJournal {
+    pub root: String,
+    pub started_at: i64,
+    /// `(local_path, target)` of every completed transfer.
+    pub completed: HashMap<Cloud, HashSet<(String, String)>>,
+}
+
+impl RunJournal {
+    pub fn is_completed(&self, cloud: &Cloud, local_path: &str, target: &str) -> bool {
+        self.completed
+            .get(cloud)
+            .map(|transfers| transfers.contains(&(local_path.to_string(), target.to_string())))
+            .unwrap_or(false)
+    }
+}
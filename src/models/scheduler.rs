@@ -1,9 +1,37 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use crate::backends::local_backend::LocalBackend;
+use crate::backends::rclone_backend::RcloneBackend;
+use crate::backends::transfer_backend::TransferBackend;
 use crate::models::config::Config;
+use crate::models::content_policy::ContentPolicy;
+use crate::models::incremental_policy::IncrementalPolicy;
+use crate::models::link_policy::LinkPolicy;
+use crate::models::retention_policy::RetentionPolicy;
+use crate::models::retry_policy::RetryPolicy;
 use crate::services::file_service;
 
+/// Format of the timestamp folder every run uploads into.
+pub const SNAPSHOT_FORMAT: &str = "%Y_%m_%d_and_%Hh_%Mm_%Ss";
+
+/// Ends the snapshot folder of a full run when incremental backups are on,
+/// the snapshots after it only hold what changed since.
+pub const FULL_SNAPSHOT_SUFFIX: &str = "_full";
+
+/// Time of the snapshot folder `name` and whether it is marked full, `None`
+/// for folders that are not snapshots.
+pub fn parse_snapshot(name: &str) -> Option<(NaiveDateTime, bool)> {
+    let (time, full) = match name.strip_suffix(FULL_SNAPSHOT_SUFFIX) {
+        Some(time) => (time, true),
+        None => (name, false),
+    };
+
+    NaiveDateTime::parse_from_str(time, SNAPSHOT_FORMAT).ok().map(|time| (time, full))
+}
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Protocol {
     Https,
@@ -13,20 +41,44 @@ pub enum Protocol {
 
 impl Protocol {}
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize, ValueEnum)]
 pub enum Cloud {
     Mega,
     GoogleDrive,
+    Local,
 }
 
 impl Cloud {
-    pub fn name(&self, config_path: &PathBuf) -> String {
-        let config_json = file_service::read_file(config_path).unwrap();
-        let config: Config = serde_json::from_str(&config_json).unwrap();
+    /// The rclone remote, or the target directory for `Cloud::Local`, set in
+    /// the config file at `config_path`.
+    pub fn name(&self, config_path: &Path) -> Result<String, String> {
+        let config_json = file_service::read_file(config_path)
+            .map_err(|error| format!("Failed to read config {}: {}", config_path.display(), error))?;
+        let config: Config = serde_json::from_str(&config_json)
+            .map_err(|error| format!("Invalid config {}: {}", config_path.display(), error))?;
 
-        match self {
+        Ok(match self {
             Cloud::Mega => config.clouds.mega,
             Cloud::GoogleDrive => config.clouds.google_drive,
+            Cloud::Local => config.clouds.local.unwrap_or_default(),
+        })
+    }
+
+    /// Fails when the config cannot be read, and for `Cloud::Local` when
+    /// `clouds.local` is missing, empty or relative, which would copy into and
+    /// prune below the working directory.
+    pub fn backend(&self, config_path: &Path, speed: f32) -> Result<Box<dyn TransferBackend>, String> {
+        match self {
+            Cloud::Local => {
+                let target = PathBuf::from(self.name(config_path)?);
+
+                if !target.is_absolute() {
+                    return Err(format!("clouds.local in {} must be an absolute directory, got {:?}", config_path.display(), target));
+                }
+
+                Ok(Box::new(LocalBackend::new(*self, target)))
+            }
+            _ => Ok(Box::new(RcloneBackend::new(*self, self.name(config_path)?, speed))),
         }
     }
 }
@@ -38,4 +90,99 @@ pub struct Scheduler {
     pub speed: f32,
     pub clouds: HashMap<Cloud, Vec<Protocol>>,
     pub root: String,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Number of files uploaded at the same time for each cloud.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// A relaunch within this many minutes resumes the interrupted run
+    /// instead of starting a new snapshot.
+    #[serde(default = "default_resume_window_minutes")]
+    pub resume_window_minutes: u64,
+    #[serde(default)]
+    pub incremental: IncrementalPolicy,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    #[serde(default)]
+    pub content: ContentPolicy,
+    #[serde(default)]
+    pub links: LinkPolicy,
+    /// Number of threads walking the directories of the template.
+    #[serde(default = "default_scan_threads")]
+    pub scan_threads: usize,
+}
+
+impl Scheduler {
+    /// Folder holding every snapshot of this scheduler, `hostname/name`.
+    pub fn snapshot_parent(&self) -> String {
+        let hostname = gethostname::gethostname().to_string_lossy().to_string();
+        format!("{}/{}", hostname, self.name)
+    }
+
+    /// Folder a run started at `time` uploads into, marked with
+    /// `FULL_SNAPSHOT_SUFFIX` when it is the `full` run of an incremental chain.
+    pub fn snapshot_root(&self, time: DateTime<Utc>, full: bool) -> String {
+        let suffix = match self.incremental.enabled && full {
+            true => FULL_SNAPSHOT_SUFFIX,
+            false => "",
+        };
+
+        format!("{}/{}{}", self.snapshot_parent(), time.format(SNAPSHOT_FORMAT), suffix)
+    }
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_resume_window_minutes() -> u64 {
+    360
+}
+
+fn default_scan_threads() -> usize {
+    std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::models::scheduler::Cloud;
+
+    #[test]
+    fn local_backend_needs_an_absolute_target() {
+        let dir = std::env::temp_dir().join(format!("watcher_backup_local_target_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for (local, accepted) in [(None, false), (Some(""), false), (Some("backups"), false), (Some("/mnt/backups"), true)] {
+            let config = dir.join("config.json");
+            let local = local.map(|local| format!(r#","local":"{}""#, local)).unwrap_or_default();
+
+            fs::write(&config, format!(r#"{{"clouds":{{"mega":"m","google_drive":"g"{}}},"paths":{{"watcher_backup":"x"}}}}"#, local)).unwrap();
+
+            assert_eq!(Cloud::Local.backend(&config, 0.0).is_ok(), accepted, "{}", local);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_config_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("watcher_backup_bad_config_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let missing = dir.join("missing.json");
+        let error = Cloud::Mega.backend(&missing, 0.0).err().unwrap();
+
+        assert!(error.starts_with(&format!("Failed to read config {}: ", missing.display())), "{}", error);
+
+        let invalid = dir.join("invalid.json");
+        fs::write(&invalid, r#"{"clouds":{}}"#).unwrap();
+
+        let error = Cloud::GoogleDrive.name(&invalid).unwrap_err();
+
+        assert!(error.starts_with(&format!("Invalid config {}: missing field", invalid.display())), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
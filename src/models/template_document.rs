@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+/// Structured (JSON or TOML) form of a template, an alternative to the
+/// `path>1[...]2[...]3[...]4[...]5[...]s` line format. Every field but `path` is
+/// optional. There is no `include`, `set` or `${NAME}` expansion, values are
+/// taken literally.
+///
+/// ```toml
+/// [[entries]]
+/// path = "C:\\Users\\me\\Documents"
+/// selected = true
+///
+/// [[entries.filters]]
+/// regex = ".*\\.docx$"
+/// deep = 2
+///
+/// [[entries.dir_priorities]]
+/// regex = "^invoices$"
+/// priority = 1
+///
+/// [[entries.excludes]]
+/// regex = "^~\\$"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateDocument {
+    pub entries: Vec<TemplateDocumentEntry>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateDocumentEntry {
+    pub path: String,
+    /// Upload everything directly inside `path`, the `s` marker of the line format.
+    pub selected: bool,
+    /// Group 1, `{regex, deep, content}(predicates)`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FilterRule>,
+    /// Group 2, `{regex, deep, priority, content}(predicates)`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dir_file_priorities: Vec<DirFilePriorityRule>,
+    /// Group 3, `{regex, deep, priority}`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dir_priorities: Vec<DirPriorityRule>,
+    /// Group 4, `{priority, content}(predicates)`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_priorities: Vec<FilePriorityRule>,
+    /// Group 5, `{regex, deep}`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<ExcludeRule>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterRule {
+    pub regex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep: Option<i8>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub predicates: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirFilePriorityRule {
+    pub regex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep: Option<i8>,
+    pub priority: usize,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub predicates: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirPriorityRule {
+    pub regex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep: Option<i8>,
+    pub priority: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilePriorityRule {
+    pub priority: usize,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub predicates: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExcludeRule {
+    pub regex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep: Option<i8>,
+}
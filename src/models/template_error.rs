@@ -0,0 +1,69 @@
+use std::{error::Error, fmt};
+
+/// Where and why a template could not be parsed. `line` and `column` are
+/// 1-based, the column counts characters. Structured templates report rule
+/// errors by `field` (e.g. `entries[2].filters[0].regex`) instead. `file`
+/// is set when the error is in a template pulled in with `include`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateError {
+    pub line: usize,
+    pub column: usize,
+    pub field: Option<String>,
+    pub file: Option<String>,
+    pub fragment: String,
+    pub expected: String,
+}
+
+impl TemplateError {
+    /// Builds an error for the fragment starting at byte `offset` of `text`.
+    pub fn at(line: usize, text: &str, offset: usize, fragment: &str, expected: &str) -> Self {
+        let column = text.get(..offset).map(|before| before.chars().count()).unwrap_or(0) + 1;
+
+        Self {
+            line,
+            column,
+            field: None,
+            file: None,
+            fragment: fragment.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    /// Builds an error for a field of a structured template.
+    pub fn in_field(field: String, fragment: &str, expected: &str) -> Self {
+        Self {
+            line: 0,
+            column: 0,
+            field: Some(field),
+            file: None,
+            fragment: fragment.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    /// The error without its line number, for reports already grouped by line.
+    /// Errors of an included template keep their own line.
+    pub fn describe(&self) -> String {
+        let position = match (self.field.as_ref(), self.file.as_ref()) {
+            (Some(field), _) => field.clone(),
+            (None, Some(file)) => format!("{}: line {}, column {}", file, self.line, self.column),
+            (None, None) => format!("column {}", self.column),
+        };
+
+        match self.fragment.is_empty() {
+            true => format!("{}: expected {}", position, self.expected),
+            false => format!("{}: expected {}, found `{}`", position, self.expected, self.fragment),
+        }
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field.is_some() || self.file.is_some() {
+            true => f.write_str(&self.describe()),
+            false => write!(f, "line {}, {}", self.line, self.describe()),
+        }
+    }
+}
+
+impl Error for TemplateError {}
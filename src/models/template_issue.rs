@@ -0,0 +1,37 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found while checking a template. `location` is the 1-based
+/// line number, or `entries[i]` for structured templates.
+#[derive(Clone, Debug)]
+pub struct TemplateIssue {
+    pub location: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl TemplateIssue {
+    pub fn error(location: &str, message: String) -> Self {
+        Self { location: location.to_string(), severity: Severity::Error, message }
+    }
+
+    pub fn warning(location: &str, message: String) -> Self {
+        Self { location: location.to_string(), severity: Severity::Warning, message }
+    }
+}
+
+impl fmt::Display for TemplateIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        write!(f, "{}: {}: {}", self.location, severity, self.message)
+    }
+}
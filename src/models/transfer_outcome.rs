@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::scheduler::Cloud;
+
+const STDERR_MAX_LINES: usize = 10;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TransferOutcome {
+    pub cloud: Cloud,
+    pub local_path: String,
+    pub target: String,
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub bytes: u64,
+    pub stderr: String,
+    pub attempts: u32,
+}
+
+impl TransferOutcome {
+    pub fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Keeps only the last lines of `stderr`, rclone repeats a lot of context
+    /// before the actual error.
+    pub fn trim_stderr(stderr: &str) -> String {
+        let lines = stderr.trim().lines().collect::<Vec<&str>>();
+        let skip = lines.len().saturating_sub(STDERR_MAX_LINES);
+
+        lines[skip..].join("\n")
+    }
+}
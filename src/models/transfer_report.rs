@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+use super::transfer_outcome::TransferOutcome;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TransferReport {
+    /// Final outcome of every transfer that eventually succeeded.
+    pub succeeded: Vec<TransferOutcome>,
+    /// Last outcome of every transfer that still failed after the final attempt.
+    pub gave_up: Vec<TransferOutcome>,
+}
+
+impl TransferReport {
+    pub fn extend(&mut self, other: TransferReport) {
+        self.succeeded.extend(other.succeeded);
+        self.gave_up.extend(other.gave_up);
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.gave_up.is_empty()
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::filesystems::file_system::FileSystem;
+use crate::models::dir_entry::DirEntry;
+use crate::models::entry_file_filter::EntryFileFilter;
+use crate::models::template_issue::TemplateIssue;
+use crate::services::template_service::{self, TemplateFormat};
+
+/// Upper bound of files and directories scanned below one entry when
+/// looking for rules that can never match.
+const MAX_SCAN_ENTRIES: usize = 100_000;
+
+/// `/`-separated paths found below an entry, relative to it, with their
+/// level (1 = direct child).
+#[derive(Default)]
+struct Scan {
+    files: Vec<(usize, String)>,
+    dirs: Vec<(usize, String)>,
+    truncated: bool,
+}
+
+/// Reports every problem of a template without running a backup, looking
+/// the entries up in `fs`.
+pub fn check(content: &str, format: TemplateFormat, origin: Option<&Path>, fs: &dyn FileSystem) -> Vec<TemplateIssue> {
+    let mut issues = vec![];
+    let mut seen: HashMap<PathBuf, String> = HashMap::new();
+
+    for (location, entry) in template_service::parse_entries(content, format, origin) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                issues.push(TemplateIssue::error(&location, error.describe()));
+                continue;
+            }
+        };
+
+        if let Some(first) = seen.get(&entry.path) {
+            issues.push(TemplateIssue::error(&location, format!("duplicate path {}, first used at {}", entry.path_to_string(), first)));
+            continue;
+        }
+
+        seen.insert(entry.path.clone(), location.clone());
+
+        check_entry(&location, &entry, fs, &mut issues);
+    }
+
+    if seen.is_empty() && issues.is_empty() {
+        issues.push(TemplateIssue::error("1", "the template has no entries".to_string()));
+    }
+
+    issues
+}
+
+fn check_entry(line: &str, entry: &DirEntry, fs: &dyn FileSystem, issues: &mut Vec<TemplateIssue>) {
+    if !fs.exists(&entry.path) {
+        issues.push(TemplateIssue::warning(line, format!("{} does not exist, the entry is skipped", entry.path_to_string())));
+        return;
+    }
+
+    if entry.is_file(fs) {
+        if entry.entry_file_filter.is_some() || entry.entry_dir_file_priority.is_some() || entry.entry_dir_priority.is_some() || entry.entry_exclude.is_some() {
+            issues.push(TemplateIssue::warning(line, "rule groups 1, 2, 3 and 5 only apply to directories, this entry is a file".to_string()));
+        }
+        return;
+    }
+
+    if entry.entry_file_priority.is_some() {
+        issues.push(TemplateIssue::warning(line, "rule group 4 only applies to files, this entry is a directory".to_string()));
+    }
+
+    if entry.entry_file_filter.is_none() && entry.entry_dir_file_priority.is_none() && entry.entry_dir_priority.is_none() && entry.entry_exclude.is_none() {
+        return;
+    }
+
+    let scan = scan(fs, &entry.path);
+
+    if scan.truncated {
+        issues.push(TemplateIssue::warning(line, format!("more than {} entries below {}, rules were not checked for matches", MAX_SCAN_ENTRIES, entry.path_to_string())));
+        return;
+    }
+
+    let filters = entry.entry_file_filter.clone().unwrap_or_default();
+
+    for filter in filters.iter() {
+        let reach = reach(filter.deep);
+
+        if !scan.files.iter().any(|(level, name)| *level <= reach && filter.regex.matches_relative(name)) {
+            issues.push(TemplateIssue::warning(line, format!("filter `{}` matches no file{}", filter.regex, depth_note(filter.deep))));
+        }
+    }
+
+    for priority in entry.entry_dir_priority.iter().flatten() {
+        let reach = reach(priority.deep);
+
+        if !scan.dirs.iter().any(|(level, _)| *level <= reach) {
+            issues.push(TemplateIssue::warning(line, format!("dir priority `{}` can never match, there is no subdirectory{}", priority.regex, depth_note(priority.deep))));
+        } else if !scan.dirs.iter().any(|(level, name)| *level <= reach && priority.regex.matches_relative(name)) {
+            issues.push(TemplateIssue::warning(line, format!("dir priority `{}` matches no directory{}", priority.regex, depth_note(priority.deep))));
+        }
+    }
+
+    for exclude in entry.entry_exclude.iter().flatten() {
+        let reach = reach(exclude.deep);
+
+        if !scan.files.iter().chain(scan.dirs.iter()).any(|(level, name)| *level <= reach && exclude.regex.matches_relative(name)) {
+            issues.push(TemplateIssue::warning(line, format!("exclude `{}` matches nothing{}", exclude.regex, depth_note(exclude.deep))));
+        }
+    }
+
+    for priority in entry.entry_dir_file_priority.iter().flatten() {
+        let reach = reach(priority.deep);
+
+        let matching = scan.files
+            .iter()
+            .filter(|(level, name)| *level <= reach && priority.regex.matches_relative(name))
+            .collect::<Vec<&(usize, String)>>();
+
+        if matching.is_empty() {
+            issues.push(TemplateIssue::warning(line, format!("dir file priority `{}` matches no file{}", priority.regex, depth_note(priority.deep))));
+        } else if !filters.is_empty() && !matching.iter().any(|(level, name)| passes_filters(&filters, *level, name)) {
+            issues.push(TemplateIssue::warning(line, format!("dir file priority `{}` is shadowed, every file it matches is dropped by the filters in group 1", priority.regex)));
+        }
+    }
+}
+
+/// Deepest level a rule with depth `deep` is compared against, the planner
+/// keeps a rule with depth `n` for `n + 1` levels below the entry.
+fn reach(deep: Option<i8>) -> usize {
+    deep.map(|deep| deep as usize + 1).unwrap_or(usize::MAX)
+}
+
+fn depth_note(deep: Option<i8>) -> String {
+    match deep {
+        Some(deep) => format!(" within depth {}", deep),
+        None => String::new(),
+    }
+}
+
+fn passes_filters(filters: &[EntryFileFilter], level: usize, name: &str) -> bool {
+    filters.iter().any(|filter| level <= reach(filter.deep) && filter.regex.matches_relative(name))
+}
+
+fn scan(fs: &dyn FileSystem, root: &Path) -> Scan {
+    let mut scan = Scan::default();
+    let mut dirs = vec![(0, root.to_path_buf(), String::new())];
+
+    while let Some((level, dir, relative)) = dirs.pop() {
+        let Ok(entries) = fs.read_dir(&dir) else {
+            continue;
+        };
+
+        for path in entries {
+            if scan.files.len() + scan.dirs.len() >= MAX_SCAN_ENTRIES {
+                scan.truncated = true;
+                return scan;
+            }
+
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let name = match relative.is_empty() {
+                true => name,
+                false => format!("{}/{}", relative, name),
+            };
+
+            if fs.is_dir(&path) {
+                scan.dirs.push((level + 1, name.clone()));
+                dirs.push((level + 1, path, name));
+            } else {
+                scan.files.push((level + 1, name));
+            }
+        }
+    }
+
+    scan
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filesystems::memory_file_system::MemoryFileSystem;
+    use crate::models::template_issue::Severity;
+    use crate::services::check_service;
+    use crate::services::template_service::TemplateFormat;
+
+    fn tree() -> MemoryFileSystem {
+        MemoryFileSystem::with_files(&[("/data/notes.txt", ""), ("/data/src/main.rs", ""), ("/flat/readme.md", "")])
+    }
+
+    /// Severity and message of every issue of `template` checked against `tree()`.
+    fn issues(template: &str) -> Vec<(Severity, String)> {
+        check_service::check(template, TemplateFormat::Lines, None, &tree())
+            .into_iter()
+            .map(|issue| (issue.severity, issue.message))
+            .collect()
+    }
+
+    fn warning(message: &str) -> Vec<(Severity, String)> {
+        vec![(Severity::Warning, message.to_string())]
+    }
+
+    #[test]
+    fn matching_rules_have_no_issues() {
+        assert!(issues(r"/data>1[{\.(txt|rs)$,}]2[{\.rs$,,1}]3[{^src$,,2}]5[{main,}]s").is_empty());
+    }
+
+    #[test]
+    fn reports_template_errors() {
+        assert_eq!(issues("/data>1[{(,}]")[0].0, Severity::Error);
+        assert_eq!(issues("/data>s\n/data>s")[0], (Severity::Error, "duplicate path /data, first used at 1".to_string()));
+        assert_eq!(issues("# nothing"), vec![(Severity::Error, "the template has no entries".to_string())]);
+    }
+
+    #[test]
+    fn reports_missing_paths_and_misplaced_groups() {
+        assert_eq!(issues("/missing>s"), warning("/missing does not exist, the entry is skipped"));
+        assert_eq!(issues(r"/data/notes.txt>1[{\.txt$,}]s"), warning("rule groups 1, 2, 3 and 5 only apply to directories, this entry is a file"));
+        assert_eq!(issues("/data>4[{1,todo}]s"), warning("rule group 4 only applies to files, this entry is a directory"));
+    }
+
+    #[test]
+    fn reports_rules_that_never_match() {
+        assert_eq!(issues(r"/data>1[{\.pdf$,}]s"), warning(r"filter `\.pdf$` matches no file"));
+        assert_eq!(issues(r"/data>1[{\.rs$,0}{\.txt$,}]s"), warning(r"filter `\.rs$` matches no file within depth 0"));
+        assert_eq!(issues("/flat>3[{^src$,,1}]s"), warning("dir priority `^src$` can never match, there is no subdirectory"));
+        assert_eq!(issues("/data>3[{^lib$,,1}]s"), warning("dir priority `^lib$` matches no directory"));
+        assert_eq!(issues("/data>5[{^tmp$,}]s"), warning("exclude `^tmp$` matches nothing"));
+        assert_eq!(issues(r"/data>2[{\.pdf$,,1}]s"), warning(r"dir file priority `\.pdf$` matches no file"));
+        assert_eq!(
+            issues(r"/data>1[{\.txt$,}]2[{\.rs$,,1}]s"),
+            warning(r"dir file priority `\.rs$` is shadowed, every file it matches is dropped by the filters in group 1")
+        );
+    }
+}
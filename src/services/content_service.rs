@@ -0,0 +1,103 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::filesystems::file_system::FileSystem;
+use crate::models::content_policy::{BinaryContent, ContentPolicy};
+use crate::models::pattern::Pattern;
+
+/// How much of the start of a file is looked at for NUL bytes, like git does.
+const BINARY_SNIFF_BYTES: usize = 8_000;
+
+/// Shortest run of printable ASCII kept when extracting strings.
+const MIN_STRING_LENGTH: usize = 4;
+
+/// Whether the first `policy.max_bytes` of the file at `path` match
+/// `pattern`, binary files being handled as `policy.binary` says.
+pub fn is_match(fs: &dyn FileSystem, path: &Path, pattern: &Pattern, policy: &ContentPolicy) -> bool {
+    let Ok(data) = read(fs, path, policy.max_bytes) else {
+        return false;
+    };
+
+    if !is_binary(&data) {
+        return pattern.is_match_bytes(&data);
+    }
+
+    match policy.binary {
+        BinaryContent::Skip => false,
+        BinaryContent::Raw => pattern.is_match_bytes(&data),
+        BinaryContent::Strings => pattern.is_match_bytes(&strings(&data)),
+    }
+}
+
+fn read(fs: &dyn FileSystem, path: &Path, max_bytes: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+
+    fs.open(path)?.take(max_bytes).read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_SNIFF_BYTES)].contains(&0)
+}
+
+/// The runs of at least `MIN_STRING_LENGTH` printable ASCII bytes, one per line.
+fn strings(data: &[u8]) -> Vec<u8> {
+    let mut strings = vec![];
+
+    for run in data.split(|byte| !(byte.is_ascii_graphic() || *byte == b' ' || *byte == b'\t')) {
+        if run.len() >= MIN_STRING_LENGTH {
+            strings.extend_from_slice(run);
+            strings.push(b'\n');
+        }
+    }
+
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::filesystems::memory_file_system::MemoryFileSystem;
+    use crate::mappers::template_to_dir_entry;
+    use crate::models::content_policy::{BinaryContent, ContentPolicy};
+    use crate::models::pattern::Pattern;
+    use crate::services::content_service;
+
+    fn tree() -> MemoryFileSystem {
+        MemoryFileSystem::with_files(&[("/data/notes.txt", "call alice\nsecret"), ("/data/blob.bin", "ab\0secret\0")])
+    }
+
+    fn is_match(path: &str, pattern: &str, max_bytes: u64, binary: BinaryContent) -> bool {
+        let policy = ContentPolicy { max_bytes, binary };
+
+        content_service::is_match(&tree(), Path::new(path), &Pattern::content(pattern).unwrap(), &policy)
+    }
+
+    #[test]
+    fn text_files_match_within_max_bytes() {
+        assert!(is_match("/data/notes.txt", "secret", 1024, BinaryContent::Skip));
+        assert!(!is_match("/data/notes.txt", "secret", 10, BinaryContent::Skip));
+    }
+
+    #[test]
+    fn binary_files_follow_the_policy() {
+        let line = "(?m)^secret$";
+
+        assert!(!is_match("/data/blob.bin", "secret", 1024, BinaryContent::Skip));
+        assert!(is_match("/data/blob.bin", "secret", 1024, BinaryContent::Raw));
+        assert!(!is_match("/data/blob.bin", line, 1024, BinaryContent::Raw));
+        assert!(is_match("/data/blob.bin", line, 1024, BinaryContent::Strings));
+        assert!(!is_match("/data/blob.bin", "ab", 1024, BinaryContent::Strings));
+    }
+
+    #[test]
+    fn content_patterns_may_match_raw_bytes() {
+        let entries = template_to_dir_entry::map(r"/data>4[{1,(?-u:\xFF)}]".to_string(), None).unwrap();
+        let content = &entries[0].entry_file_priority.as_ref().unwrap()[0].content;
+
+        assert!(content.is_match_bytes(b"a\xFFb"));
+        assert!(!content.is_match_bytes(b"ab"));
+    }
+}
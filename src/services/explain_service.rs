@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use crate::filesystems::file_system::FileSystem;
+use crate::mappers::{dir_entry_to_commands, dir_entry_to_template};
+use crate::models::scheduler::Scheduler;
+use crate::models::template_error::TemplateError;
+use crate::services::template_service::{self, TemplateFormat};
+
+/// Report of how the planner treats `path`: the template entries above it,
+/// written back in the line format, then how their rules apply to it and
+/// whether it is planned.
+pub fn explain(
+    content: &str,
+    format: TemplateFormat,
+    origin: Option<&Path>,
+    scheduler: &Scheduler,
+    fs: &dyn FileSystem,
+    path: &Path,
+) -> Result<String, TemplateError> {
+    let entries = template_service::map(content.to_string(), format, origin)?;
+    let mut report = format!("Explaining {}\n\nTemplate entries:\n", path.display());
+
+    let related = template_service::parse_entries(content, format, origin)
+        .into_iter()
+        .filter_map(|(location, entry)| entry.ok().map(|entry| (location, entry)))
+        .filter(|(_, entry)| path.starts_with(&entry.path))
+        .collect::<Vec<_>>();
+
+    if related.is_empty() {
+        report.push_str("  none, no entry is the path or one of its parents\n");
+    }
+
+    for (location, entry) in related.iter() {
+        report.push_str(&format!("  {}: {}", location, dir_entry_to_template::map(std::slice::from_ref(entry))));
+    }
+
+    report.push_str("\nRules:\n");
+
+    for step in dir_entry_to_commands::explain(entries, fs, &scheduler.content, scheduler.links, scheduler.scan_threads, path) {
+        report.push_str(&format!("  {}\n", step));
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+
+use crate::models::command::Command;
+use crate::models::file_index::{FileIndex, FileState};
+use crate::services::file_service;
+
+/// Index path for a scheduler file, `daily.json` -> `daily.index`.
+pub fn index_path(scheduler_path: &Path) -> PathBuf {
+    scheduler_path.with_extension("index")
+}
+
+pub fn load(path: &Path) -> FileIndex {
+    file_service::read_file(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(path: &Path, index: &FileIndex) -> std::io::Result<()> {
+    fs::write(path, serde_json::to_string(index)?)
+}
+
+/// Current state of every command whose file is new or changed compared to
+/// `index`. Hashes are only computed when `with_hash` is set, and only for
+/// files whose size or modification time moved. Recorded links are compared
+/// by the link itself, not by what it points to.
+pub fn changed_states(commands: &[Command], index: &FileIndex, with_hash: bool) -> HashMap<String, FileState> {
+    let mut states = HashMap::new();
+
+    for command in commands {
+        let Ok(mut state) = file_state(Path::new(&command.local_path), command.link) else {
+            continue;
+        };
+
+        let previous = index.files.get(&command.local_path);
+
+        if previous.map(|previous| previous.same_metadata(&state)).unwrap_or(false) {
+            continue;
+        }
+
+        if with_hash {
+            state.hash = file_hash(Path::new(&command.local_path), command.link).ok();
+
+            if previous.is_some_and(|previous| previous.hash.is_some() && previous.hash == state.hash) {
+                continue;
+            }
+        }
+
+        states.insert(command.local_path.clone(), state);
+    }
+
+    states
+}
+
+fn file_state(path: &Path, link: bool) -> std::io::Result<FileState> {
+    let metadata = match link {
+        true => fs::symlink_metadata(path)?,
+        false => fs::metadata(path)?,
+    };
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0);
+
+    Ok(FileState {
+        size: metadata.len(),
+        modified,
+        hash: None,
+    })
+}
+
+fn file_hash(path: &Path, link: bool) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+
+    if link {
+        hasher.update(fs::read_link(path)?.to_string_lossy().as_bytes());
+    } else {
+        let mut file = File::open(path)?;
+        let mut buffer = [0u8; 64 * 1024];
+
+        loop {
+            let read = file.read(&mut buffer)?;
+
+            if read == 0 {
+                break;
+            }
+
+            hasher.update(&buffer[..read]);
+        }
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::models::command::Command;
+    use crate::models::file_index::FileIndex;
+    use crate::services::index_service;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watcher_backup_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn command(path: &Path, link: bool) -> Command {
+        Command {
+            local_path: path.to_string_lossy().to_string(),
+            remote_path: String::new(),
+            priority: None,
+            link,
+        }
+    }
+
+    fn touch(path: &Path, seconds: u64) {
+        File::options().write(true).open(path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+    }
+
+    /// Names of the files `changed_states` returns, sorted.
+    fn changed(commands: &[Command], index: &FileIndex, with_hash: bool) -> Vec<String> {
+        let mut names = index_service::changed_states(commands, index, with_hash)
+            .into_keys()
+            .map(|path| Path::new(&path).file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn only_new_and_changed_files_are_returned() {
+        let dir = temp_dir("index_changed");
+
+        for name in ["same.txt", "grown.txt", "touched.txt"] {
+            fs::write(dir.join(name), "a").unwrap();
+            touch(&dir.join(name), 1_700_000_000);
+        }
+
+        let commands = ["same.txt", "grown.txt", "touched.txt", "missing.txt"].map(|name| command(&dir.join(name), false));
+        let index = FileIndex {
+            runs_since_full: 0,
+            files: index_service::changed_states(&commands, &FileIndex::default(), false),
+        };
+
+        assert_eq!(changed(&commands, &FileIndex::default(), false), ["grown.txt", "same.txt", "touched.txt"]);
+        assert!(changed(&commands, &index, false).is_empty());
+
+        fs::write(dir.join("grown.txt"), "ab").unwrap();
+        touch(&dir.join("grown.txt"), 1_700_000_000);
+        touch(&dir.join("touched.txt"), 1_700_000_100);
+        fs::write(dir.join("new.txt"), "a").unwrap();
+
+        let commands = ["same.txt", "grown.txt", "touched.txt", "new.txt"].map(|name| command(&dir.join(name), false));
+
+        assert_eq!(changed(&commands, &index, false), ["grown.txt", "new.txt", "touched.txt"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_skip_touched_but_unchanged_files() {
+        let dir = temp_dir("index_hashes");
+
+        for name in ["touched.txt", "edited.txt", "unhashed.txt"] {
+            fs::write(dir.join(name), "abc").unwrap();
+            touch(&dir.join(name), 1_700_000_000);
+        }
+
+        let commands = ["touched.txt", "edited.txt", "unhashed.txt"].map(|name| command(&dir.join(name), false));
+        let mut index = FileIndex {
+            runs_since_full: 0,
+            files: index_service::changed_states(&commands, &FileIndex::default(), true),
+        };
+
+        assert!(index.files.values().all(|state| state.hash.as_deref() == Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")));
+
+        index.files.get_mut(&commands[2].local_path).unwrap().hash = None;
+        fs::write(dir.join("edited.txt"), "abd").unwrap();
+
+        for name in ["touched.txt", "edited.txt", "unhashed.txt"] {
+            touch(&dir.join(name), 1_700_000_100);
+        }
+
+        assert_eq!(changed(&commands, &index, true), ["edited.txt", "unhashed.txt"]);
+        assert_eq!(changed(&commands, &index, false), ["edited.txt", "touched.txt", "unhashed.txt"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recorded_links_are_compared_by_the_link_itself() {
+        let dir = temp_dir("index_links");
+
+        fs::write(dir.join("target.txt"), "abc").unwrap();
+        std::os::unix::fs::symlink(dir.join("target.txt"), dir.join("recorded")).unwrap();
+        std::os::unix::fs::symlink(dir.join("target.txt"), dir.join("followed")).unwrap();
+
+        let commands = [command(&dir.join("recorded"), true), command(&dir.join("followed"), false)];
+        let index = FileIndex {
+            runs_since_full: 0,
+            files: index_service::changed_states(&commands, &FileIndex::default(), true),
+        };
+        let target = dir.join("target.txt").to_string_lossy().to_string();
+
+        assert_eq!(index.files[&commands[0].local_path].size, target.len() as u64);
+        assert_eq!(index.files[&commands[1].local_path].size, 3);
+
+        fs::write(dir.join("target.txt"), "abcd").unwrap();
+
+        assert_eq!(changed(&commands, &index, true), ["followed"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::Utc;
+
+use crate::models::run_journal::{JournalRecord, RunJournal};
+use crate::models::scheduler::Cloud;
+use crate::services::file_service;
+
+/// Appends completed transfers to the journal of the current run.
+pub struct JournalWriter {
+    file: Mutex<File>,
+}
+
+impl JournalWriter {
+    pub fn record(&self, cloud: Cloud, local_path: &str, target: &str) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        write_record(&mut file, &JournalRecord::Completed { cloud, local_path: local_path.to_string(), target: target.to_string() })
+    }
+}
+
+/// Journal path for a scheduler file, `daily.json` -> `daily.journal`.
+pub fn journal_path(scheduler_path: &Path) -> PathBuf {
+    scheduler_path.with_extension("journal")
+}
+
+/// Resumes the journal at `path` if it was started less than
+/// `window_minutes` ago, otherwise starts a new one rooted at `new_root`.
+pub fn open(path: &Path, window_minutes: u64, new_root: String) -> std::io::Result<(RunJournal, JournalWriter)> {
+    let now = Utc::now().timestamp();
+
+    if let Some(journal) = load(path) {
+        if now - journal.started_at <= (window_minutes * 60) as i64 {
+            let file = OpenOptions::new().append(true).open(path)?;
+            return Ok((journal, JournalWriter { file: Mutex::new(file) }));
+        }
+    }
+
+    let journal = RunJournal {
+        root: new_root,
+        started_at: now,
+        ..Default::default()
+    };
+
+    let mut file = File::create(path)?;
+    write_record(&mut file, &JournalRecord::Start { root: journal.root.clone(), started_at: now })?;
+
+    Ok((journal, JournalWriter { file: Mutex::new(file) }))
+}
+
+/// Removes the journal once every transfer of the run has succeeded.
+pub fn finish(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+fn load(path: &Path) -> Option<RunJournal> {
+    let content = file_service::read_file(path).ok()?;
+    let mut journal: Option<RunJournal> = None;
+
+    for line in content.lines() {
+        // A torn last line means the run was killed mid-write, skip it.
+        let Ok(record) = serde_json::from_str::<JournalRecord>(line) else {
+            continue;
+        };
+
+        match record {
+            JournalRecord::Start { root, started_at } => {
+                journal = Some(RunJournal { root, started_at, ..Default::default() });
+            }
+            JournalRecord::Completed { cloud, local_path, target } => {
+                if let Some(journal) = journal.as_mut() {
+                    journal.completed.entry(cloud).or_default().insert((local_path, target));
+                }
+            }
+        }
+    }
+
+    journal
+}
+
+fn write_record(file: &mut File, record: &JournalRecord) -> std::io::Result<()> {
+    let line = serde_json::to_string(record)?;
+
+    writeln!(file, "{}", line)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::models::scheduler::Cloud;
+    use crate::services::journal_service;
+
+    #[test]
+    fn resumes_only_transfers_to_the_same_target() {
+        let path = std::env::temp_dir().join(format!("watcher_backup_journal_{}.journal", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (journal, writer) = journal_service::open(&path, 60, "host/daily/first".to_string()).unwrap();
+        writer.record(Cloud::Mega, "/home/a.txt", "mega:host/daily/first/home/a.txt").unwrap();
+        drop(writer);
+
+        let (resumed, _) = journal_service::open(&path, 60, "host/daily/second".to_string()).unwrap();
+
+        assert_eq!(resumed.root, journal.root);
+        assert!(resumed.is_completed(&Cloud::Mega, "/home/a.txt", "mega:host/daily/first/home/a.txt"));
+        assert!(!resumed.is_completed(&Cloud::Mega, "/home/a.txt", "mega:host/daily/first/home/moved/a.txt"));
+        assert!(!resumed.is_completed(&Cloud::GoogleDrive, "/home/a.txt", "mega:host/daily/first/home/a.txt"));
+
+        fs::remove_file(path).unwrap();
+    }
+}
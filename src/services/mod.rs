@@ -1 +1,12 @@
-pub mod file_service;
\ No newline at end of file
+pub mod file_service;
+pub mod plan_service;
+pub mod report_service;
+pub mod transfer_service;
+pub mod journal_service;
+pub mod index_service;
+pub mod retention_service;
+pub mod restore_service;
+pub mod check_service;
+pub mod template_service;
+pub mod content_service;
+pub mod explain_service;
\ No newline at end of file
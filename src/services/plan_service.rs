@@ -0,0 +1,65 @@
+use clap::ValueEnum;
+
+use crate::models::plan_entry::PlanEntry;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum PlanFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+pub fn render(plan: &[PlanEntry], format: PlanFormat) -> Result<String, serde_json::Error> {
+    match format {
+        PlanFormat::Table => Ok(render_table(plan)),
+        PlanFormat::Json => serde_json::to_string_pretty(plan),
+    }
+}
+
+fn render_table(plan: &[PlanEntry]) -> String {
+    let header = ["#", "PRIORITY", "CLOUD", "LOCAL PATH", "REMOTE PATH"];
+
+    let rows = plan
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            [
+                (index + 1).to_string(),
+                entry.priority.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string()),
+                format!("{:?}", entry.cloud),
+                entry.local_path.clone(),
+                entry.remote_path.clone(),
+            ]
+        })
+        .collect::<Vec<[String; 5]>>();
+
+    let mut widths = header.map(|column| column.len());
+
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+
+    table.push_str(&format_row(&header.map(|column| column.to_string()), &widths));
+
+    for row in rows.iter() {
+        table.push_str(&format_row(row, &widths));
+    }
+
+    table.push_str(&format!("{} file(s) planned\n", plan.len()));
+
+    table
+}
+
+fn format_row(row: &[String; 5], widths: &[usize; 5]) -> String {
+    let cells = row
+        .iter()
+        .zip(widths.iter())
+        .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+        .collect::<Vec<String>>();
+
+    format!("{}\n", cells.join("  ").trim_end())
+}
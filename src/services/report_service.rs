@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use crate::models::transfer_report::TransferReport;
+
+pub fn summary(report: &TransferReport, elapsed: Duration) -> String {
+    let total = report.succeeded.len() + report.gave_up.len();
+    let retried = report.succeeded.iter().filter(|outcome| outcome.attempts > 1).count();
+    let bytes: u64 = report.succeeded.iter().map(|outcome| outcome.bytes).sum();
+
+    let mut summary = format!(
+        "{} transfer(s): {} succeeded ({} after retry), {} gave up, {} byte(s) in {:.1}s\n",
+        total,
+        report.succeeded.len(),
+        retried,
+        report.gave_up.len(),
+        bytes,
+        elapsed.as_secs_f32()
+    );
+
+    for outcome in report.gave_up.iter() {
+        let exit_code = outcome.exit_code.map(|code| code.to_string()).unwrap_or_else(|| "none".to_string());
+
+        summary.push_str(&format!(
+            "GAVE UP [{:?}] {} -> {} (exit code {}, {} attempt(s))\n",
+            outcome.cloud, outcome.local_path, outcome.target, exit_code, outcome.attempts
+        ));
+
+        for line in outcome.stderr.lines() {
+            summary.push_str(&format!("    {}\n", line));
+        }
+    }
+
+    summary
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::backends::transfer_backend::TransferBackend;
+use crate::mappers::dir_entry_to_commands;
+use crate::models::scheduler::{self, Cloud};
+use crate::models::transfer_outcome::TransferOutcome;
+use crate::models::transfer_report::TransferReport;
+
+pub const LATEST: &str = "latest";
+
+/// Resolves `snapshot` to the folder names under `parent` to restore from,
+/// oldest first. `latest` picks the most recent snapshot. With `incremental`
+/// backups a snapshot only holds the files that changed, so it comes after
+/// every snapshot back to the full one it builds on.
+pub fn resolve_snapshots(backend: &dyn TransferBackend, parent: &str, snapshot: &str, incremental: bool) -> Result<Vec<String>, String> {
+    let names = backend.list_snapshots(parent).map_err(|error| error.to_string())?;
+
+    let mut snapshots = names
+        .iter()
+        .filter_map(|name| scheduler::parse_snapshot(name).map(|(time, full)| (time, full, name.clone())))
+        .collect::<Vec<(NaiveDateTime, bool, String)>>();
+
+    snapshots.sort_by_key(|(time, _, _)| *time);
+
+    let chosen = match snapshot == LATEST {
+        true => snapshots.len().checked_sub(1).ok_or_else(|| format!("No snapshot found under {}", parent))?,
+        false => match snapshots.iter().position(|(_, _, name)| name == snapshot) {
+            Some(index) => index,
+            None if names.iter().any(|name| name == snapshot) => return Ok(vec![snapshot.to_string()]),
+            None => return Err(format!("Snapshot {} not found under {}", snapshot, parent)),
+        },
+    };
+
+    if !incremental {
+        return Ok(vec![snapshots[chosen].2.clone()]);
+    }
+
+    let full = snapshots[..=chosen].iter().rposition(|(_, full, _)| *full).ok_or_else(|| {
+        format!("Snapshot {} is incremental and no full snapshot precedes it under {}", snapshots[chosen].2, parent)
+    })?;
+
+    Ok(snapshots[full..=chosen].iter().map(|(_, _, name)| name.clone()).collect())
+}
+
+/// Copies every file of the snapshots at `roots`, oldest first, whose
+/// original path matches `filter` back to its original location, or below
+/// `target` when set. A file held by several snapshots comes from the newest.
+pub fn restore(
+    cloud: Cloud,
+    backend: &dyn TransferBackend,
+    roots: &[String],
+    filter: Option<&Regex>,
+    target: Option<&Path>,
+) -> Result<TransferReport, String> {
+    let mut files = BTreeMap::new();
+
+    for root in roots {
+        for remote_file in backend.list_files(root).map_err(|error| error.to_string())? {
+            files.insert(remote_file, root);
+        }
+    }
+
+    let mut report = TransferReport::default();
+
+    for (remote_file, root) in files {
+        let original = dir_entry_to_commands::local_path(&remote_file);
+
+        if let Some(filter) = filter {
+            if !filter.is_match(&original.to_string_lossy()) {
+                continue;
+            }
+        }
+
+        let destination = match target {
+            Some(target) => remote_file.split('/').fold(PathBuf::from(target), |path, segment| path.join(segment)),
+            None => original,
+        };
+
+        let started = Instant::now();
+        let result = backend.download(root, &remote_file, &destination);
+
+        let outcome = TransferOutcome {
+            cloud,
+            local_path: destination.to_string_lossy().to_string(),
+            target: format!("{}/{}", root, remote_file),
+            exit_code: if result.is_ok() { Some(0) } else { None },
+            duration: started.elapsed(),
+            bytes: fs::metadata(&destination).map(|meta| meta.len()).unwrap_or(0),
+            stderr: result.err().map(|error| error.to_string()).unwrap_or_default(),
+            attempts: 1,
+        };
+
+        match outcome.is_success() {
+            true => report.succeeded.push(outcome),
+            false => report.gave_up.push(outcome),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use regex::Regex;
+
+    use crate::backends::transfer_backend::TransferBackend;
+    use crate::models::command::Command;
+    use crate::models::scheduler::Cloud;
+    use crate::models::transfer_outcome::TransferOutcome;
+    use crate::services::restore_service;
+
+    /// Snapshot folders under `host/daily`, each holding remote files and
+    /// their content.
+    struct Snapshots(BTreeMap<String, Vec<(&'static str, &'static str)>>);
+
+    impl Snapshots {
+        fn new(snapshots: &[(&str, &[(&'static str, &'static str)])]) -> Self {
+            Self(snapshots.iter().map(|(name, files)| (format!("host/daily/{}", name), files.to_vec())).collect())
+        }
+    }
+
+    impl TransferBackend for Snapshots {
+        fn transfer(&self, _: &Command, _: &str) -> TransferOutcome {
+            unreachable!()
+        }
+
+        fn target(&self, _: &Command, _: &str) -> String {
+            unreachable!()
+        }
+
+        fn list_snapshots(&self, parent: &str) -> std::io::Result<Vec<String>> {
+            Ok(self.0.keys().filter_map(|root| root.strip_prefix(&format!("{}/", parent)).map(str::to_string)).collect())
+        }
+
+        fn purge(&self, _: &str) -> std::io::Result<()> {
+            unreachable!()
+        }
+
+        fn list_files(&self, root: &str) -> std::io::Result<Vec<String>> {
+            Ok(self.0[root].iter().map(|(file, _)| file.to_string()).collect())
+        }
+
+        fn download(&self, root: &str, remote_file: &str, destination: &Path) -> std::io::Result<()> {
+            let (_, content) = self.0[root].iter().find(|(file, _)| *file == remote_file).unwrap();
+
+            fs::create_dir_all(destination.parent().unwrap())?;
+            fs::write(destination, content)
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watcher_backup_{}_{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn chain() -> Snapshots {
+        Snapshots::new(&[
+            ("2024_05_01_and_12h_00m_00s_full", &[("data/a.txt", "a1"), ("data/b.txt", "b1")]),
+            ("2024_05_02_and_12h_00m_00s", &[("data/a.txt", "a2")]),
+            ("2024_05_03_and_12h_00m_00s_full", &[("data/a.txt", "a3"), ("data/b.txt", "b3")]),
+            ("2024_05_04_and_12h_00m_00s", &[("data/b.txt", "b4"), ("data/sub/c.txt", "c4")]),
+            ("2024_05_05_and_12h_00m_00s", &[("data/a.txt", "a5")]),
+            ("notes", &[]),
+        ])
+    }
+
+    #[test]
+    fn incremental_snapshots_resolve_back_to_their_full_snapshot() {
+        let backend = chain();
+        let resolve = |snapshot: &str, incremental: bool| restore_service::resolve_snapshots(&backend, "host/daily", snapshot, incremental);
+
+        assert_eq!(
+            resolve("latest", true).unwrap(),
+            ["2024_05_03_and_12h_00m_00s_full", "2024_05_04_and_12h_00m_00s", "2024_05_05_and_12h_00m_00s"]
+        );
+        assert_eq!(resolve("2024_05_02_and_12h_00m_00s", true).unwrap(), ["2024_05_01_and_12h_00m_00s_full", "2024_05_02_and_12h_00m_00s"]);
+        assert_eq!(resolve("2024_05_03_and_12h_00m_00s_full", true).unwrap(), ["2024_05_03_and_12h_00m_00s_full"]);
+        assert_eq!(resolve("latest", false).unwrap(), ["2024_05_05_and_12h_00m_00s"]);
+        assert_eq!(resolve("2024_05_04_and_12h_00m_00s", false).unwrap(), ["2024_05_04_and_12h_00m_00s"]);
+        assert_eq!(resolve("notes", true).unwrap(), ["notes"]);
+        assert_eq!(resolve("2024_05_06_and_12h_00m_00s", true).unwrap_err(), "Snapshot 2024_05_06_and_12h_00m_00s not found under host/daily");
+    }
+
+    #[test]
+    fn incremental_snapshot_without_full_one_is_an_error() {
+        let backend = Snapshots::new(&[("2024_05_01_and_12h_00m_00s", &[]), ("2024_05_02_and_12h_00m_00s", &[])]);
+
+        assert_eq!(
+            restore_service::resolve_snapshots(&backend, "host/daily", "latest", true).unwrap_err(),
+            "Snapshot 2024_05_02_and_12h_00m_00s is incremental and no full snapshot precedes it under host/daily"
+        );
+        assert_eq!(
+            restore_service::resolve_snapshots(&Snapshots::new(&[]), "host/daily", "latest", true).unwrap_err(),
+            "No snapshot found under host/daily"
+        );
+    }
+
+    #[test]
+    fn restores_each_file_from_the_newest_snapshot_holding_it() {
+        let dir = temp_dir("restore_chain");
+        let backend = chain();
+        let roots = restore_service::resolve_snapshots(&backend, "host/daily", "latest", true)
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| format!("host/daily/{}", snapshot))
+            .collect::<Vec<String>>();
+
+        let report = restore_service::restore(Cloud::Local, &backend, &roots, None, Some(&dir)).unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(fs::read_to_string(dir.join("data/a.txt")).unwrap(), "a5");
+        assert_eq!(fs::read_to_string(dir.join("data/b.txt")).unwrap(), "b4");
+        assert_eq!(fs::read_to_string(dir.join("data/sub/c.txt")).unwrap(), "c4");
+        assert_eq!(
+            report.succeeded.iter().map(|outcome| outcome.target.as_str()).collect::<Vec<&str>>(),
+            [
+                "host/daily/2024_05_05_and_12h_00m_00s/data/a.txt",
+                "host/daily/2024_05_04_and_12h_00m_00s/data/b.txt",
+                "host/daily/2024_05_04_and_12h_00m_00s/data/sub/c.txt",
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let filter = Regex::new(r"b\.txt$").unwrap();
+        let report = restore_service::restore(Cloud::Local, &backend, &roots, Some(&filter), Some(&dir)).unwrap();
+
+        assert_eq!(report.succeeded.len(), 1);
+        assert!(!dir.join("data/a.txt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashSet;
+
+use chrono::{Datelike, NaiveDateTime, TimeDelta};
+
+use crate::backends::transfer_backend::TransferBackend;
+use crate::models::retention_policy::RetentionPolicy;
+use crate::models::scheduler;
+
+/// Lists the snapshot folders under `parent` and purges the ones the policy
+/// does not keep. Returns the full remote paths of the pruned snapshots.
+/// Without a policy the backend is not touched at all.
+pub fn prune(
+    backend: &dyn TransferBackend,
+    parent: &str,
+    policy: &RetentionPolicy,
+    incremental: bool,
+    now: NaiveDateTime,
+    dry_run: bool,
+) -> std::io::Result<Vec<String>> {
+    if !policy.is_enabled() {
+        return Ok(vec![]);
+    }
+
+    let names = backend.list_snapshots(parent)?;
+    let mut pruned = vec![];
+
+    for name in expired(&names, policy, incremental, now) {
+        let path = format!("{}/{}", parent, name);
+
+        if !dry_run {
+            backend.purge(&path)?;
+        }
+
+        pruned.push(path);
+    }
+
+    Ok(pruned)
+}
+
+/// Snapshot names outside `policy`, folders that are not snapshots are never
+/// touched. With `incremental` backups a kept snapshot also keeps every older
+/// one back to the full snapshot it builds on, or all of them when there is
+/// none, since they hold the only copy of the files it did not upload.
+pub fn expired(names: &[String], policy: &RetentionPolicy, incremental: bool, now: NaiveDateTime) -> Vec<String> {
+    if !policy.is_enabled() {
+        return vec![];
+    }
+
+    let mut snapshots = names
+        .iter()
+        .filter_map(|name| scheduler::parse_snapshot(name).map(|(time, full)| (time, full, name.clone())))
+        .collect::<Vec<(NaiveDateTime, bool, String)>>();
+
+    if snapshots.is_empty() {
+        return vec![];
+    }
+
+    snapshots.sort_by_key(|(time, _, _)| std::cmp::Reverse(*time));
+
+    let mut keep: HashSet<usize> = HashSet::new();
+
+    match policy.has_keep_rules() {
+        true => {
+            if let Some(count) = policy.keep_last {
+                keep.extend(0..count.min(snapshots.len()));
+            }
+
+            keep_by_period(&snapshots, policy.keep_daily, &mut keep, |time| {
+                (time.year(), time.ordinal(), 0)
+            });
+            keep_by_period(&snapshots, policy.keep_weekly, &mut keep, |time| {
+                (time.iso_week().year(), time.iso_week().week(), 0)
+            });
+            keep_by_period(&snapshots, policy.keep_monthly, &mut keep, |time| {
+                (time.year(), time.month(), 0)
+            });
+        }
+        false => keep.extend(0..snapshots.len()),
+    }
+
+    if let Some(days) = policy.max_age_days {
+        if let Some(age) = TimeDelta::try_days(days) {
+            let oldest = now - age;
+
+            keep.retain(|index| snapshots[*index].0 >= oldest);
+        }
+    }
+
+    keep.insert(0);
+
+    if incremental {
+        for index in keep.clone() {
+            let full = snapshots[index..]
+                .iter()
+                .position(|(_, full, _)| *full)
+                .map(|offset| index + offset)
+                .unwrap_or(snapshots.len() - 1);
+
+            keep.extend(index..=full);
+        }
+    }
+
+    snapshots
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !keep.contains(index))
+        .map(|(_, (_, _, name))| name)
+        .collect()
+}
+
+/// Keeps the newest snapshot of each of the `count` most recent periods.
+fn keep_by_period<F>(snapshots: &[(NaiveDateTime, bool, String)], count: Option<usize>, keep: &mut HashSet<usize>, period: F)
+where
+    F: Fn(&NaiveDateTime) -> (i32, u32, u32),
+{
+    let Some(count) = count else {
+        return;
+    };
+
+    let mut seen = HashSet::new();
+
+    for (index, (time, _, _)) in snapshots.iter().enumerate() {
+        if seen.len() >= count {
+            break;
+        }
+
+        if seen.insert(period(time)) {
+            keep.insert(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::{NaiveDateTime, TimeZone, Utc};
+
+    use crate::backends::transfer_backend::TransferBackend;
+    use crate::models::command::Command;
+    use crate::models::file_index::{FileIndex, FileState};
+    use crate::models::incremental_policy::IncrementalPolicy;
+    use crate::models::retention_policy::RetentionPolicy;
+    use crate::models::scheduler::Scheduler;
+    use crate::models::transfer_outcome::TransferOutcome;
+    use crate::services::retention_service;
+
+    /// A remote that cannot be reached.
+    struct Offline;
+
+    impl TransferBackend for Offline {
+        fn transfer(&self, _: &Command, _: &str) -> TransferOutcome {
+            unreachable!()
+        }
+
+        fn target(&self, _: &Command, _: &str) -> String {
+            unreachable!()
+        }
+
+        fn list_snapshots(&self, _: &str) -> std::io::Result<Vec<String>> {
+            Err(std::io::Error::other("offline"))
+        }
+
+        fn purge(&self, _: &str) -> std::io::Result<()> {
+            Err(std::io::Error::other("offline"))
+        }
+
+        fn list_files(&self, _: &str) -> std::io::Result<Vec<String>> {
+            Err(std::io::Error::other("offline"))
+        }
+
+        fn download(&self, _: &str, _: &str, _: &Path) -> std::io::Result<()> {
+            Err(std::io::Error::other("offline"))
+        }
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024_06_01_and_12h_00m_00s", "%Y_%m_%d_and_%Hh_%Mm_%Ss").unwrap()
+    }
+
+    #[test]
+    fn disabled_policy_does_not_reach_the_backend() {
+        assert!(retention_service::prune(&Offline, "host/daily", &RetentionPolicy::default(), false, now(), false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn enabled_policy_reports_listing_failures() {
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            ..Default::default()
+        };
+
+        assert!(retention_service::prune(&Offline, "host/daily", &policy, false, now(), false).is_err());
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn incremental_snapshots_keep_their_chain_to_the_full_one() {
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            ..Default::default()
+        };
+        let snapshots = names(&[
+            "2024_05_28_and_12h_00m_00s_full",
+            "2024_05_29_and_12h_00m_00s",
+            "2024_05_30_and_12h_00m_00s_full",
+            "2024_05_31_and_12h_00m_00s",
+            "2024_06_01_and_12h_00m_00s",
+        ]);
+
+        assert_eq!(
+            retention_service::expired(&snapshots, &policy, true, now()),
+            names(&["2024_05_29_and_12h_00m_00s", "2024_05_28_and_12h_00m_00s_full"])
+        );
+        assert_eq!(retention_service::expired(&snapshots, &policy, false, now()).len(), 4);
+    }
+
+    #[test]
+    fn incremental_snapshots_without_a_full_one_are_all_kept() {
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            ..Default::default()
+        };
+        let snapshots = names(&["2024_05_31_and_12h_00m_00s", "2024_06_01_and_12h_00m_00s", "notes"]);
+
+        assert!(retention_service::expired(&snapshots, &policy, true, now()).is_empty());
+    }
+
+    #[test]
+    fn no_snapshots_expire_nothing() {
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            ..Default::default()
+        };
+
+        assert!(retention_service::expired(&[], &policy, true, now()).is_empty());
+        assert!(retention_service::expired(&names(&["notes"]), &policy, true, now()).is_empty());
+    }
+
+    #[test]
+    fn incremental_chains_start_with_a_full_snapshot() {
+        let scheduler = Scheduler {
+            name: "daily".to_string(),
+            incremental: IncrementalPolicy {
+                enabled: true,
+                full_every: 2,
+                hash: false,
+            },
+            ..Default::default()
+        };
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            ..Default::default()
+        };
+        let mut index = FileIndex::default();
+        let mut roots = vec![];
+
+        for day in 1..=4 {
+            let full = scheduler.incremental.is_full_run(&index);
+            let time = Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap();
+
+            roots.push(scheduler.snapshot_root(time, full).rsplit('/').next().unwrap().to_string());
+            index.files.insert(format!("/data/{}.txt", day), FileState { size: 1, modified: 0, hash: None });
+            index.runs_since_full = if full { 0 } else { index.runs_since_full + 1 };
+        }
+
+        assert_eq!(
+            roots,
+            names(&["2024_05_01_and_12h_00m_00s_full", "2024_05_02_and_12h_00m_00s", "2024_05_03_and_12h_00m_00s_full", "2024_05_04_and_12h_00m_00s"])
+        );
+        assert_eq!(retention_service::expired(&roots, &policy, true, now()), names(&["2024_05_02_and_12h_00m_00s", "2024_05_01_and_12h_00m_00s_full"]));
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::mappers::{
+    dir_entry_to_template, dir_entry_to_template_document, template_document_to_dir_entry,
+    template_to_dir_entry,
+};
+use crate::models::dir_entry::DirEntry;
+use crate::models::template_document::TemplateDocument;
+use crate::models::template_error::TemplateError;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TemplateFormat {
+    /// `path>1[{...}]2[{...}]3[{...}]4[{...}]5[{...}]s`, one entry per line
+    Lines,
+    Json,
+    Toml,
+}
+
+impl TemplateFormat {
+    /// `.json` and `.toml` templates are structured, anything else uses the line format.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()) {
+            Some(extension) if extension == "json" => TemplateFormat::Json,
+            Some(extension) if extension == "toml" => TemplateFormat::Toml,
+            _ => TemplateFormat::Lines,
+        }
+    }
+}
+
+/// `origin` is the template file when it was read from disk, line templates
+/// resolve their `include` directives next to it.
+pub fn map(content: String, format: TemplateFormat, origin: Option<&Path>) -> Result<Vec<DirEntry>, TemplateError> {
+    match format {
+        TemplateFormat::Lines => template_to_dir_entry::map(content, origin),
+        _ => template_document_to_dir_entry::map(document(&content, format)?),
+    }
+}
+
+/// Writes `entries` in `format`, the inverse of `map`.
+pub fn render(entries: &[DirEntry], format: TemplateFormat) -> Result<String, String> {
+    let document = dir_entry_to_template_document::map(entries);
+
+    match format {
+        TemplateFormat::Lines => Ok(dir_entry_to_template::map(entries)),
+        TemplateFormat::Json => serde_json::to_string_pretty(&document).map_err(|error| error.to_string()),
+        TemplateFormat::Toml => toml::to_string_pretty(&document).map_err(|error| error.to_string()),
+    }
+}
+
+/// Whether two templates describe exactly the same entries and rules.
+pub fn same_entries(left: &[DirEntry], right: &[DirEntry]) -> bool {
+    match (serde_json::to_value(left), serde_json::to_value(right)) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// Parses every entry independently, pairing each result with where it is
+/// in the template: its line number, or `entries[i]` for structured formats.
+pub fn parse_entries(content: &str, format: TemplateFormat, origin: Option<&Path>) -> Vec<(String, Result<DirEntry, TemplateError>)> {
+    if format == TemplateFormat::Lines {
+        return template_to_dir_entry::parse_lines(content, origin)
+            .into_iter()
+            .map(|(line, entry)| (line.to_string(), entry))
+            .collect();
+    }
+
+    match document(content, format) {
+        Ok(document) => document
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                (template_document_to_dir_entry::location(index), template_document_to_dir_entry::map_entry(index, entry))
+            })
+            .collect(),
+        Err(error) => vec![(error.line.to_string(), Err(error))],
+    }
+}
+
+pub fn document(content: &str, format: TemplateFormat) -> Result<TemplateDocument, TemplateError> {
+    match format {
+        TemplateFormat::Json => serde_json::from_str(content).map_err(|error| {
+            let message = error.to_string();
+            let message = message.split(" at line ").next().unwrap_or_default();
+
+            TemplateError::at(error.line(), line_of(content, error.line()), error.column().saturating_sub(1), "", &format!("a valid JSON template ({})", message))
+        }),
+        _ => toml::from_str(content).map_err(|error| {
+            let offset = error.span().map(|span| span.start).unwrap_or(0).min(content.len());
+            let line = content[..offset].matches('\n').count() + 1;
+            let line_start = content[..offset].rfind('\n').map(|index| index + 1).unwrap_or(0);
+
+            TemplateError::at(line, &content[line_start..], offset - line_start, "", &format!("a valid TOML template ({})", error.message()))
+        }),
+    }
+}
+
+fn line_of(content: &str, line: usize) -> &str {
+    content.lines().nth(line.saturating_sub(1)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::template_service::{self, TemplateFormat};
+
+    /// Canonical lines, written back exactly as they are read.
+    const LINES: &[&str] = &[
+        r"/data>1[{\.txt$,2,secret}]s",
+        r"/data>1[{\.md$,}]",
+        r"/data>2[{\.rs$,1,3,fn main}]",
+        r"/data>2[{\.rs$,,3}]",
+        r"/data>3[{^src$,0,2}]",
+        r"/data>3[{^src$,,2}]",
+        r"/data>4[{5,password}]s",
+        r"/data>4[{5}]",
+        r"/data>5[{^target$,1}]",
+        r"/data>5[{^target$,}]s",
+        r#""/a>b">1[{"a,b",}]"#,
+        r#"/data>1[{"x}y",,"has ""quotes"""}]"#,
+        r#"/data>1[{"glob:*.{pdf,doc}",}]2[{glob:reports/**/*.pdf,,1}]5[{re:^\.git$,}]"#,
+        r"/data>1[{\.log$,,error}(size<2GB,age<7d)]2[{.*,3,1}(modified>=2024-01-01)]4[{1}(size>1MB)]",
+        r"/data>1[{\.txt$,}]2[{\.rs$,,1}]3[{^src$,,2}]4[{3,x}]5[{^tmp$,}]s",
+    ];
+
+    #[test]
+    fn line_templates_are_written_back_unchanged() {
+        for line in LINES {
+            let entries = template_service::map(line.to_string(), TemplateFormat::Lines, None).unwrap();
+
+            assert_eq!(template_service::render(&entries, TemplateFormat::Lines).unwrap(), format!("{}\n", line));
+        }
+    }
+
+    #[test]
+    fn every_rule_survives_structured_formats_both_ways() {
+        for line in LINES {
+            let entries = template_service::map(line.to_string(), TemplateFormat::Lines, None).unwrap();
+
+            for format in [TemplateFormat::Json, TemplateFormat::Toml] {
+                let rendered = template_service::render(&entries, format).unwrap();
+                let structured = template_service::map(rendered.clone(), format, None).unwrap();
+
+                assert!(template_service::same_entries(&entries, &structured), "{} as {:?}:\n{}", line, format, rendered);
+                assert_eq!(template_service::render(&structured, format).unwrap(), rendered, "{} as {:?}", line, format);
+
+                let back = template_service::render(&structured, TemplateFormat::Lines).unwrap();
+
+                assert_eq!(back, format!("{}\n", line), "{} from {:?}", line, format);
+            }
+        }
+    }
+
+    #[test]
+    fn structured_templates_survive_the_line_format() {
+        let json = r#"{"entries":[{"path":"/a>b/${x}","selected":true,"filters":[{"regex":"x}y","deep":1,"content":" \\$\\{HOME\\}","predicates":["size<2GB"]}],"excludes":[{"regex":"glob:*.{tmp,bak}"}]}]}"#;
+        let toml = "[[entries]]\npath = \"/data\"\nselected = false\n\n[[entries.file_priorities]]\npriority = 2\ncontent = \"a,b\"\n";
+
+        for (document, format) in [(json, TemplateFormat::Json), (toml, TemplateFormat::Toml)] {
+            let entries = template_service::map(document.to_string(), format, None).unwrap();
+            let lines = template_service::render(&entries, TemplateFormat::Lines).unwrap();
+            let back = template_service::map(lines.clone(), TemplateFormat::Lines, None).unwrap();
+
+            assert!(template_service::same_entries(&entries, &back), "{:?} as lines:\n{}", format, lines);
+        }
+    }
+}
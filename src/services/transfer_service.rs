@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::thread::{self, sleep};
+
+use crate::backends::transfer_backend::TransferBackend;
+use crate::models::{
+    command::Command, retry_policy::RetryPolicy, transfer_outcome::TransferOutcome,
+    transfer_report::TransferReport,
+};
+
+/// Uploads `commands` in order, then retries the failures by priority with
+/// exponential backoff until they succeed or run out of attempts.
+/// `on_success` is called as soon as each transfer succeeds.
+pub fn run(
+    backend: &dyn TransferBackend,
+    commands: &[Command],
+    root: &str,
+    policy: &RetryPolicy,
+    concurrency: usize,
+    on_success: &(dyn Fn(&TransferOutcome) + Sync),
+) -> TransferReport {
+    let mut report = TransferReport::default();
+    let mut queue = commands.iter().collect::<Vec<&Command>>();
+    let mut attempt = 1;
+
+    while !queue.is_empty() {
+        if attempt > 1 {
+            sleep(policy.delay(attempt));
+        }
+
+        let mut failed = vec![];
+
+        for (command, mut outcome) in transfer_all(backend, queue, root, concurrency, on_success) {
+            outcome.attempts = attempt;
+
+            match outcome.is_success() {
+                true => report.succeeded.push(outcome),
+                false if attempt >= policy.max_attempts => report.gave_up.push(outcome),
+                false => failed.push(command),
+            }
+        }
+
+        failed.sort_by_key(|command| command.priority.unwrap_or(usize::MAX));
+
+        queue = failed;
+        attempt += 1;
+    }
+
+    report
+}
+
+/// Runs `queue` through a pool of `concurrency` workers. Workers always take
+/// the next command from the front, so uploads start in queue order, and the
+/// results are returned in that same order.
+fn transfer_all<'a>(
+    backend: &dyn TransferBackend,
+    queue: Vec<&'a Command>,
+    root: &str,
+    concurrency: usize,
+    on_success: &(dyn Fn(&TransferOutcome) + Sync),
+) -> Vec<(&'a Command, TransferOutcome)> {
+    let workers = concurrency.clamp(1, queue.len().max(1));
+    let pending = Mutex::new(queue.into_iter().enumerate().collect::<VecDeque<(usize, &Command)>>());
+    let finished = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let next = pending.lock().unwrap().pop_front();
+
+                let Some((index, command)) = next else {
+                    break;
+                };
+
+                let outcome = backend.transfer(command, root);
+
+                if outcome.is_success() {
+                    on_success(&outcome);
+                }
+
+                finished.lock().unwrap().push((index, command, outcome));
+            });
+        }
+    });
+
+    let mut finished = finished.into_inner().unwrap();
+    finished.sort_by_key(|(index, _, _)| *index);
+
+    finished.into_iter().map(|(_, command, outcome)| (command, outcome)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Mutex;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crate::backends::transfer_backend::TransferBackend;
+    use crate::models::command::Command;
+    use crate::models::retry_policy::RetryPolicy;
+    use crate::models::scheduler::Cloud;
+    use crate::models::transfer_outcome::TransferOutcome;
+    use crate::services::transfer_service;
+
+    /// Fails the first attempts of chosen files and records when each
+    /// transfer starts and how many run at once.
+    #[derive(Default)]
+    struct Flaky {
+        failures: HashMap<String, u32>,
+        pause: Duration,
+        attempts: Mutex<HashMap<String, u32>>,
+        started: Mutex<Vec<String>>,
+        running: Mutex<(usize, usize)>,
+    }
+
+    impl Flaky {
+        fn failing(failures: &[(&str, u32)]) -> Self {
+            Self {
+                failures: failures.iter().map(|(path, count)| (path.to_string(), *count)).collect(),
+                ..Default::default()
+            }
+        }
+
+        fn started(&self) -> Vec<String> {
+            self.started.lock().unwrap().clone()
+        }
+    }
+
+    impl TransferBackend for Flaky {
+        fn transfer(&self, command: &Command, _: &str) -> TransferOutcome {
+            self.started.lock().unwrap().push(command.local_path.clone());
+
+            {
+                let mut running = self.running.lock().unwrap();
+                running.0 += 1;
+                running.1 = running.1.max(running.0);
+            }
+
+            sleep(self.pause);
+
+            let attempt = {
+                let mut attempts = self.attempts.lock().unwrap();
+                let attempt = attempts.entry(command.local_path.clone()).or_default();
+                *attempt += 1;
+                *attempt
+            };
+
+            self.running.lock().unwrap().0 -= 1;
+
+            let failed = attempt <= self.failures.get(&command.local_path).copied().unwrap_or(0);
+
+            TransferOutcome {
+                cloud: Cloud::Local,
+                local_path: command.local_path.clone(),
+                target: command.local_path.clone(),
+                exit_code: Some(failed as i32),
+                duration: Duration::ZERO,
+                bytes: 0,
+                stderr: match failed {
+                    true => format!("attempt {} failed", attempt),
+                    false => String::new(),
+                },
+                attempts: 1,
+            }
+        }
+
+        fn target(&self, command: &Command, _: &str) -> String {
+            command.local_path.clone()
+        }
+
+        fn list_snapshots(&self, _: &str) -> std::io::Result<Vec<String>> {
+            unreachable!()
+        }
+
+        fn purge(&self, _: &str) -> std::io::Result<()> {
+            unreachable!()
+        }
+
+        fn list_files(&self, _: &str) -> std::io::Result<Vec<String>> {
+            unreachable!()
+        }
+
+        fn download(&self, _: &str, _: &str, _: &Path) -> std::io::Result<()> {
+            unreachable!()
+        }
+    }
+
+    fn commands(commands: &[(&str, Option<usize>)]) -> Vec<Command> {
+        commands
+            .iter()
+            .map(|(path, priority)| Command {
+                local_path: path.to_string(),
+                remote_path: String::new(),
+                priority: *priority,
+                link: false,
+            })
+            .collect()
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay_ms: 0,
+            max_delay_ms: 0,
+        }
+    }
+
+    fn attempts(outcomes: &[TransferOutcome]) -> Vec<(&str, u32)> {
+        outcomes.iter().map(|outcome| (outcome.local_path.as_str(), outcome.attempts)).collect()
+    }
+
+    #[test]
+    fn failures_are_retried_by_priority() {
+        let backend = Flaky::failing(&[("c", 1), ("a", 1), ("b", 2)]);
+        let commands = commands(&[("c", None), ("a", Some(3)), ("d", Some(2)), ("b", Some(1))]);
+        let succeeded = Mutex::new(vec![]);
+
+        let report = transfer_service::run(&backend, &commands, "root", &policy(3), 1, &|outcome| {
+            succeeded.lock().unwrap().push(outcome.local_path.clone())
+        });
+
+        assert_eq!(backend.started(), ["c", "a", "d", "b", "b", "a", "c", "b"]);
+        assert_eq!(attempts(&report.succeeded), [("d", 1), ("a", 2), ("c", 2), ("b", 3)]);
+        assert!(report.gave_up.is_empty());
+        assert_eq!(succeeded.into_inner().unwrap(), ["d", "a", "c", "b"]);
+    }
+
+    #[test]
+    fn files_failing_every_attempt_are_given_up() {
+        let backend = Flaky::failing(&[("a", 5), ("b", 1)]);
+        let commands = commands(&[("a", Some(1)), ("b", Some(2)), ("c", None)]);
+        let succeeded = Mutex::new(vec![]);
+
+        let report = transfer_service::run(&backend, &commands, "root", &policy(2), 2, &|outcome| {
+            succeeded.lock().unwrap().push(outcome.local_path.clone())
+        });
+
+        assert_eq!(attempts(&report.succeeded), [("c", 1), ("b", 2)]);
+        assert_eq!(attempts(&report.gave_up), [("a", 2)]);
+        assert_eq!(report.gave_up[0].stderr, "attempt 2 failed");
+        assert!(!report.is_success());
+        assert_eq!(backend.started().iter().filter(|path| *path == "a").count(), 2);
+        assert!(!succeeded.into_inner().unwrap().contains(&"a".to_string()));
+    }
+
+    #[test]
+    fn workers_start_in_queue_order() {
+        let backend = Flaky {
+            pause: Duration::from_millis(20),
+            ..Flaky::failing(&[("f01", 1), ("f07", 1), ("f04", 1)])
+        };
+        let paths = (0..12).map(|index| format!("f{:02}", index)).collect::<Vec<String>>();
+        let commands = commands(&paths.iter().enumerate().map(|(index, path)| (path.as_str(), Some(index))).collect::<Vec<_>>());
+
+        let report = transfer_service::run(&backend, &commands, "root", &policy(2), 3, &|_| {});
+        let started = backend.started();
+
+        for (position, path) in started[..12].iter().enumerate() {
+            let queued = paths.iter().position(|queued| queued == path).unwrap();
+
+            assert!(position.abs_diff(queued) < 3, "{} started {}th: {:?}", path, position, started);
+        }
+
+        let mut retried = started[12..].to_vec();
+        retried.sort();
+
+        assert_eq!(retried, ["f01", "f04", "f07"]);
+        assert_eq!(
+            attempts(&report.succeeded),
+            [
+                ("f00", 1), ("f02", 1), ("f03", 1), ("f05", 1), ("f06", 1), ("f08", 1), ("f09", 1), ("f10", 1), ("f11", 1),
+                ("f01", 2), ("f04", 2), ("f07", 2),
+            ]
+        );
+        assert!((2..=3).contains(&backend.running.lock().unwrap().1));
+    }
+}